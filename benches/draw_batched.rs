@@ -0,0 +1,60 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use egui::{pos2, vec2, CentralPanel, Color32, ColorImage, Context, RawInput, Rect, TextureHandle, Ui};
+use yanimator::anim_parser::{AnimationCel, DrawOptions, OAM};
+
+const GRID_SIZE: usize = 16;
+const THUMBNAIL_SIZE: f32 = 20.0;
+
+// One palette of plain tiles, what's in them doesn't change how long drawing takes
+fn load_textures(ctx: &Context) -> Vec<Vec<TextureHandle>> {
+    vec![(0..1024).map(|i| {
+        ctx.load_texture(format!("tile_{}", i), ColorImage::new([8, 8], Color32::WHITE), Default::default())
+    }).collect()]
+}
+
+// A grid of 256 cels with 8 overlapping 16x16 OAMs each, like a whole project in the cel list
+fn cel_grid() -> Vec<AnimationCel> {
+    (0..GRID_SIZE * GRID_SIZE).map(|i| AnimationCel {
+        name: format!("cel_{}", i),
+        oams: (0..8).map(|j| OAM { x: j * 6, y: j * 3, tile: (i * 8 + j as usize) % 960, ..Default::default() }).collect()
+    }).collect()
+}
+
+fn thumbnail_rect(i: usize) -> Rect {
+    Rect::from_min_size(pos2((i % GRID_SIZE) as f32, (i / GRID_SIZE) as f32) * THUMBNAIL_SIZE, vec2(THUMBNAIL_SIZE, THUMBNAIL_SIZE))
+}
+
+fn run_frame(ctx: &Context, mut add_contents: impl FnMut(&mut Ui)) {
+    let input = RawInput {
+        screen_rect: Some(Rect::from_min_size(pos2(0.0, 0.0), vec2(2048.0, 2048.0))),
+        ..Default::default()
+    };
+
+    let _ = ctx.run(input, |ctx| {
+        CentralPanel::default().show(ctx, |ui| add_contents(ui));
+    });
+}
+
+fn draw_cel_grid(c: &mut Criterion) {
+    let ctx = Context::default();
+    let textures = load_textures(&ctx);
+    let cels = cel_grid();
+    let thumbnails: Vec<(&AnimationCel, Rect)> = cels.iter().enumerate().map(|(i, cel)| (cel, thumbnail_rect(i))).collect();
+
+    let mut group = c.benchmark_group("cel_grid_256");
+
+    group.bench_function("draw", |b| b.iter(|| run_frame(&ctx, |ui| {
+        for cel in &cels {
+            cel.draw(&textures, ui, DrawOptions::default());
+        }
+    })));
+
+    group.bench_function("draw_batched", |b| b.iter(|| run_frame(&ctx, |ui| {
+        AnimationCel::draw_batched(&thumbnails, &textures, ui);
+    })));
+
+    group.finish();
+}
+
+criterion_group!(benches, draw_cel_grid);
+criterion_main!(benches);
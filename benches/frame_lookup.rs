@@ -0,0 +1,55 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use yanimator::anim_parser::Animation;
+
+const FRAME_COUNT: usize = 2500;
+const FRAME_DURATION: u16 = 4;
+
+// 2500 frames of 4 ticks, a 10k tick animation
+fn long_animation() -> Animation {
+    let frames: Vec<(String, u16)> = (0..FRAME_COUNT).map(|i| (format!("cel_{}", i % 16), FRAME_DURATION)).collect();
+    Animation::from_frame_list("anim_long", &frames)
+}
+
+// The walk over the frames get_anim_frame_from_frames did before the frame ends were cached
+fn linear_lookup(animation: &Animation, frames: usize) -> usize {
+    if frames == 0 {return 0;}
+
+    let mut end = 0;
+
+    for (i, frame) in animation.frames.iter().enumerate() {
+        end += frame.duration as usize;
+
+        if frames <= end {
+            return i;
+        }
+    }
+
+    0
+}
+
+fn frame_lookup(c: &mut Criterion) {
+    let mut animation = long_animation();
+    let total_ticks = animation.get_total_frames();
+
+    // Near the end, where the walk is slowest and a timeline scrubbed there spends most of its time
+    let tick = total_ticks - 1;
+
+    let mut group = c.benchmark_group("frame_lookup_10k_ticks");
+
+    group.bench_function("linear", |b| b.iter(|| linear_lookup(&animation, black_box(tick))));
+
+    group.bench_function("get_anim_frame_from_frames", |b| b.iter(|| animation.get_anim_frame_from_frames(black_box(tick))));
+
+    // Worst case, an edit before every lookup so the frame ends are rebuilt each time
+    group.bench_function("get_anim_frame_from_frames_after_edit", |b| b.iter(|| {
+        animation.frames_changed();
+        animation.get_anim_frame_from_frames(black_box(tick))
+    }));
+
+    group.finish();
+}
+
+criterion_group!(benches, frame_lookup);
+criterion_main!(benches);
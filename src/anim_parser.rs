@@ -1,22 +1,26 @@
+use std::{borrow::Cow, collections::{HashMap, HashSet}, hash::{Hash, Hasher}, io::Read, ops::RangeInclusive, sync::OnceLock};
 
-use egui::{pos2, vec2, Color32, Rect, Stroke, TextureHandle, Ui};
+use egui::{pos2, vec2, Align2, Color32, FontId, Mesh, Pos2, Rect, Stroke, TextureHandle, Ui, Vec2};
+use image::{Rgba, RgbaImage};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+use crate::{palette_parser::Palette, sprite_parser::Spritesheet};
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum OAMShape {
     Square,
     Horizontal,
     Vertical
 }
-#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum OAMSize {
     Size0,
     Size1,
     Size2,
     Size3
 }
-#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum OAMFlip {
     None,
     Horizontal,
@@ -24,22 +28,315 @@ pub enum OAMFlip {
     Both
 }
 
+// OBJ mode bits 10-11 of attribute 0
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize, Default)]
+pub enum ObjMode {
+    #[default]
+    Normal,
+    SemiTransparent,
+    // Masks the OBJ window instead of being drawn
+    Window,
+    Prohibited
+}
+
+// Byte order of the attribute words, OAM::new takes big-endian words while ROM and OAM memory are little-endian
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Endianness {
+    Big,
+    Little
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct OAM {
     pub shape: OAMShape,
     pub size: OAMSize,
     pub flip: OAMFlip,
-    pub x: i8,
-    pub y: i8,
+    pub x: i16,
+    pub y: i16,
     pub palette: usize,
     pub tile: usize,
     #[serde(skip)]
     pub selected: bool,
     #[serde(default = "usize::default")]
-    pub zindex: usize
+    pub zindex: usize,
+    // Preview only color multiplied over the sprite, doesn't touch the palette
+    #[serde(skip)]
+    pub tint: Option<Color32>,
+    // Cleared by the OBJ disable bit, disabled OAMs aren't drawn
+    #[serde(default = "enabled_default", skip_serializing_if = "is_true")]
+    pub enabled: bool,
+    // Set when rotation/scaling is turned on in attribute 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affine: Option<OAMAffine>,
+    // Set when decoding hit a shape or size value it didn't know and fell back to a default
+    #[serde(skip)]
+    pub shape_size_was_coerced: bool,
+    #[serde(default, skip_serializing_if = "is_default_obj_mode")]
+    pub obj_mode: ObjMode,
+    // Editor only grouping, OAMs with the same layer name can be moved and hidden together
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    // Set by hiding the OAM's layer, unlike enabled this never reaches the exported OAM words
+    #[serde(skip)]
+    pub hidden: bool
+}
+
+// The scale isn't stored in the OAM words, the matrix lives in a separate parameter table
+// so it only affects the preview
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct OAMAffine {
+    pub index: u8,
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub double_size: bool
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+// Fields left at their default aren't written, so projects that don't use them stay the same as before
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_default_obj_mode(obj_mode: &ObjMode) -> bool {
+    *obj_mode == ObjMode::default()
 }
 
 const SPRITE_SIZE: f32 = 20.0;
+const MAX_NAME_LENGTH: usize = 255;
+
+// Hardware X is 9 bits and Y is 8 bits
+pub const OAM_X_RANGE: RangeInclusive<i16> = -256..=255;
+pub const OAM_Y_RANGE: RangeInclusive<i16> = -128..=127;
+// Visible GBA display in pixels
+pub const SCREEN_WIDTH: i16 = 240;
+pub const SCREEN_HEIGHT: i16 = 160;
+// OBJ palette number is 4 bits
+pub const MAX_PALETTE: usize = 15;
+// Entries in OAM, every sprite on screen at once has to fit in these
+pub const MAX_HARDWARE_OAMS: usize = 128;
+// Tile number in attribute 2 is 12 bits, the same width to_oam_words masks it to
+pub const MAX_TILE: usize = 0x0FFF;
+
+// FNV-1a, unlike DefaultHasher it isn't randomly seeded so content hashes are the same across runs.
+// Integers are always written little-endian so hashes don't depend on the platform either
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> StableHasher {
+        StableHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) { self.write(&i.to_le_bytes()) }
+    fn write_u32(&mut self, i: u32) { self.write(&i.to_le_bytes()) }
+    fn write_u64(&mut self, i: u64) { self.write(&i.to_le_bytes()) }
+    fn write_usize(&mut self, i: usize) { self.write_u64(i as u64) }
+    fn write_i16(&mut self, i: i16) { self.write(&i.to_le_bytes()) }
+    fn write_i32(&mut self, i: i32) { self.write(&i.to_le_bytes()) }
+    fn write_i64(&mut self, i: i64) { self.write(&i.to_le_bytes()) }
+    fn write_isize(&mut self, i: isize) { self.write_i64(i as i64) }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct DrawOptions {
+    pub selection_indicator: bool,
+    // Crosshair and (x, y) label at the origin of every OAM
+    pub coordinate_gizmos: bool,
+    // Magenta checkerboard where a tile can't be drawn because its palette or tile isn't loaded
+    pub missing_placeholder: bool,
+    // OAM index and zindex in the bottom right corner of every drawn OAM
+    pub debug_labels: bool,
+    // Outline of the 240x160 display, with the cel origin at the top left of the screen
+    pub safe_area: bool,
+    // Fades every OAM's tint towards transparent, from 0 (invisible) to 1. None draws at full opacity
+    pub opacity: Option<f32>
+}
+
+fn draw_missing_placeholder(ui: &mut Ui, rect: Rect) {
+    let cell_size = rect.size() / 2.0;
+
+    for (cx, cy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        let color = if (cx + cy) % 2 == 0 { Color32::from_rgb(255, 0, 255) } else { Color32::BLACK };
+        let cell = Rect::from_min_size(rect.min + vec2(cx as f32, cy as f32) * cell_size, cell_size);
+
+        ui.painter().rect_filled(cell, 0, color);
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+    // Data ended in the middle of an OAM or frame
+    Truncated,
+    // Not a single 0xXXXX word in the input, which almost always means it's the wrong kind of text
+    NoWords,
+    // Word at this byte offset of the source couldn't be read as hex
+    InvalidWord { offset: usize },
+    BadDuration(String),
+    BadCelIndex(String),
+    // A name or frame entry was never closed
+    MissingTerminator,
+    // No { opening the array of frames
+    MissingArray,
+    InvalidName,
+    // The length in an animation's header doesn't match the frame data that follows it
+    LengthMismatch { declared: usize, actual: usize },
+    // Reading from a stream failed for a reason other than running out of data
+    Io(String)
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Truncated => write!(f, "data ended unexpectedly"),
+            ParseError::NoWords => write!(f, "no OAM words found"),
+            ParseError::InvalidWord { offset } => write!(f, "invalid OAM word at offset {}", offset),
+            ParseError::BadDuration(duration) => write!(f, "invalid frame duration \"{}\"", duration),
+            ParseError::BadCelIndex(index) => write!(f, "cel index \"{}\" is not in the cel table", index),
+            ParseError::MissingTerminator => write!(f, "missing terminator"),
+            ParseError::MissingArray => write!(f, "no {{ opening the frame array"),
+            ParseError::InvalidName => write!(f, "name is empty or contains invalid characters"),
+            ParseError::LengthMismatch { declared, actual } => write!(f, "header declares {} bytes of frames but {} follow", declared, actual),
+            ParseError::Io(error) => write!(f, "read failed: {}", error)
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, PartialEq)]
+pub enum WriteError {
+    // The frame records of an animation add up to more bytes than the u16 length in its .bin header can hold
+    FramesTooLong { length: usize },
+    // A cel's OAM count is a single byte in its .bin
+    TooManyOAMs { count: usize },
+    // OAM palettes are a single byte in a cel's .bin
+    PaletteOutOfRange { palette: usize },
+    // from_bin stops looking for the name terminator after MAX_NAME_LENGTH bytes
+    NameTooLong { length: usize }
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::FramesTooLong { length } => write!(f, "{} bytes of frames don't fit in the {} byte limit of a .bin header", length, u16::MAX),
+            WriteError::TooManyOAMs { count } => write!(f, "{} OAMs don't fit in a cel, the limit is {}", count, u8::MAX),
+            WriteError::PaletteOutOfRange { palette } => write!(f, "palette {} is past the limit of {}", palette, u8::MAX),
+            WriteError::NameTooLong { length } => write!(f, "name is {} bytes long, the limit is {}", length, MAX_NAME_LENGTH)
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+impl From<std::io::Error> for ParseError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => ParseError::Truncated,
+            _ => ParseError::Io(error.to_string())
+        }
+    }
+}
+
+fn read_bytes<R: Read>(reader: &mut R, bytes: &mut Vec<u8>, length: usize) -> Result<(), ParseError> {
+    let start = bytes.len();
+    bytes.resize(start + length, 0x00);
+    reader.read_exact(&mut bytes[start..])?;
+
+    Ok(())
+}
+
+// Reads a name up to and including its 0x00 separator byte
+fn read_name_bytes<R: Read>(reader: &mut R, bytes: &mut Vec<u8>) -> Result<(), ParseError> {
+    for _ in 0..=MAX_NAME_LENGTH {
+        read_bytes(reader, bytes, 1)?;
+
+        if bytes[bytes.len() - 1] == 0x00 {
+            return Ok(());
+        }
+    }
+
+    Err(ParseError::InvalidName)
+}
+
+// Offset of the first byte where written differs from source, including one running out before the other
+fn first_mismatch(source: &[u8], written: &[u8]) -> Option<usize> {
+    match source.iter().zip(written).position(|(a, b)| a != b) {
+        Some(offset) => Some(offset),
+        None if source.len() != written.len() => Some(source.len().min(written.len())),
+        None => None
+    }
+}
+
+// Names are written as UTF-8, invalid sequences become U+FFFD instead of failing the whole parse
+fn decode_name(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+// Same fields as hash_content, so selection and preview state never make two OAMs differ
+impl PartialEq for OAM {
+    fn eq(&self, other: &Self) -> bool {
+        self.shape == other.shape
+            && self.size == other.size
+            && self.flip == other.flip
+            && self.x == other.x
+            && self.y == other.y
+            && self.palette == other.palette
+            && self.tile == other.tile
+            && self.zindex == other.zindex
+            && self.enabled == other.enabled
+            && self.affine == other.affine
+            && self.obj_mode == other.obj_mode
+            && self.layer == other.layer
+    }
+}
+
+impl Default for OAM {
+    fn default() -> Self {
+        OAM {
+            shape: OAMShape::Square,
+            size: OAMSize::Size1,
+            flip: OAMFlip::None,
+            x: 0,
+            y: 0,
+            palette: 0,
+            tile: 0,
+            selected: false,
+            zindex: 0,
+            tint: None,
+            enabled: true,
+            affine: None,
+            shape_size_was_coerced: false,
+            obj_mode: ObjMode::Normal,
+            layer: None,
+            hidden: false
+        }
+    }
+}
 
 impl OAM {
     pub fn new(bytes: &[u8]) -> OAM {
@@ -48,16 +345,21 @@ impl OAM {
         let word2 = ((bytes[2] as u16) << 8) | (bytes[3] as u16);
         let word3 = ((bytes[4] as u16) << 8) | (bytes[5] as u16);
         
-        // TODO: probably throw a warning if shape/size are invalid
-        
-        let shape = match word1 >> 0xc {
-            0x0 => OAMShape::Square,
-            0x4 => OAMShape::Horizontal,
-            0x8 => OAMShape::Vertical,
-            _ => OAMShape::Square
+        let mut shape_size_was_coerced = false;
+
+        // Shape is bits 14-15, bits 12 and 13 below it are the mosaic and 8bpp flags
+        let shape = match (word1 >> 14) & 0x3 {
+            0 => OAMShape::Square,
+            1 => OAMShape::Horizontal,
+            2 => OAMShape::Vertical,
+            _ => {
+                shape_size_was_coerced = true;
+                OAMShape::Square
+            }
         };
 
-        let mut y= (word1 & 0x0FFF) as i16;
+        // Y is 8 bits and X is 9 bits on hardware, both signed relative to the cel origin
+        let mut y = (word1 & 0x00FF) as i16;
         if y >= 0x80 {
             y -= 0x100;
         }
@@ -71,7 +373,7 @@ impl OAM {
             _ => OAMSize::Size0
         };
 
-        let flip = match flip_size_nibble - (flip_size_nibble & !0x3) {
+        let mut flip = match flip_size_nibble - (flip_size_nibble & !0x3) {
             0x0 => OAMFlip::None,
             0x1 => OAMFlip::Horizontal,
             0x2 => OAMFlip::Vertical,
@@ -79,12 +381,35 @@ impl OAM {
             _ => OAMFlip::None
         };
         
-        let mut x = (word2 & 0x0FFF) as i16;
+        let mut x = (word2 & 0x01FF) as i16;
 
-        if x >= 0x80 {
+        if x >= 0x100 {
             x -= 0x200;
         }
+
+        // Bit 9 of attribute 0 disables the object, unless bit 8 turns on rotation/scaling
+        // in which case it means double size instead
+        let enabled = !(word1 & 0x0100 == 0 && word1 & 0x0200 != 0);
+
+        // With rotation/scaling on, the flip bits are part of the 5 bit affine parameter index
+        let mut affine = None;
+        if word1 & 0x0100 != 0 {
+            flip = OAMFlip::None;
+            affine = Some(OAMAffine {
+                index: ((word2 >> 9) & 0x1F) as u8,
+                scale_x: 1.0,
+                scale_y: 1.0,
+                double_size: word1 & 0x0200 != 0
+            });
+        }
         
+        let obj_mode = match (word1 >> 10) & 0x3 {
+            0x0 => ObjMode::Normal,
+            0x1 => ObjMode::SemiTransparent,
+            0x2 => ObjMode::Window,
+            _ => ObjMode::Prohibited
+        };
+
         let palette = (word3 >> 0xc) as usize;
         let tile = (word3 & 0x0FFF) as usize;
         
@@ -92,16 +417,114 @@ impl OAM {
             shape,
             size,
             flip,
-            x: x as i8,
-            y: y as i8,
+            x,
+            y,
             palette,
             tile,
-            selected: false,
-            zindex: 0
+            enabled,
+            affine,
+            shape_size_was_coerced,
+            obj_mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn to_oam_words(&self) -> [u16; 3] {
+        let shape: u16 = match self.shape {
+            OAMShape::Square => 0x0000,
+            OAMShape::Horizontal => 0x4000,
+            OAMShape::Vertical => 0x8000
+        };
+
+        let mut flip_size_nibble: u16 = match self.size {
+            OAMSize::Size0 => 0x0000,
+            OAMSize::Size1 => 0x4000,
+            OAMSize::Size2 => 0x8000,
+            OAMSize::Size3 => 0xC000,
+        };
+
+        match self.flip {
+            OAMFlip::None => {},
+            OAMFlip::Horizontal => {flip_size_nibble += 0x1000},
+            OAMFlip::Vertical => {flip_size_nibble += 0x2000},
+            OAMFlip::Both => {flip_size_nibble += 0x3000}
+        };
+
+        // Casting to u16 keeps the two's complement bits, masking trims them to the hardware widths
+        let y = (self.y as u16) & 0x00FF;
+        let x = (self.x as u16) & 0x01FF;
+
+        let mut disable: u16 = if self.enabled { 0x0000 } else { 0x0200 };
+
+        if let Some(affine) = self.affine {
+            disable = 0x0100 | if affine.double_size { 0x0200 } else { 0x0000 };
+            flip_size_nibble = (flip_size_nibble & 0xC000) | ((affine.index as u16 & 0x1F) << 9);
+        }
+
+        let obj_mode: u16 = match self.obj_mode {
+            ObjMode::Normal => 0x0000,
+            ObjMode::SemiTransparent => 0x0400,
+            ObjMode::Window => 0x0800,
+            ObjMode::Prohibited => 0x0C00
+        };
+
+        // Clamped rather than masked so an out of range palette stays on the last one instead of wrapping around
+        let palette = (self.palette.min(MAX_PALETTE) as u16) << 12;
+        let tile = (self.tile & 0x0FFF) as u16;
+
+        [shape | obj_mode | disable | y, flip_size_nibble | x, palette | tile]
+    }
+
+    // Disabled, hidden and OBJ window OAMs don't show up as sprites
+    pub fn is_drawn(&self) -> bool {
+        self.enabled && !self.hidden && self.obj_mode != ObjMode::Window
+    }
+
+    // Palettes past 15 can't be stored in the OAM words and won't have a texture to draw with
+    pub fn is_palette_valid(&self) -> bool {
+        self.palette <= MAX_PALETTE
+    }
+
+    // Inverse of OAM::new
+    pub fn to_oam_bytes(&self) -> [u8; 6] {
+        let [word1, word2, word3] = self.to_oam_words();
+
+        [
+            (word1 >> 8) as u8, (word1 & 0xFF) as u8,
+            (word2 >> 8) as u8, (word2 & 0xFF) as u8,
+            (word3 >> 8) as u8, (word3 & 0xFF) as u8
+        ]
+    }
+
+    // Hardware OAM entry as it sits in OAM memory: attributes 0, 1 and 2 as little-endian
+    // words followed by the 2 filler bytes that are used for the affine parameters
+    pub fn to_hardware_oam(&self) -> [u8; 8] {
+        let [word1, word2, word3] = self.to_oam_words();
+
+        [
+            (word1 & 0xFF) as u8, (word1 >> 8) as u8,
+            (word2 & 0xFF) as u8, (word2 >> 8) as u8,
+            (word3 & 0xFF) as u8, (word3 >> 8) as u8,
+            0x00, 0x00
+        ]
+    }
+
+    pub fn new_with_endianness(bytes: &[u8], endianness: Endianness) -> OAM {
+        match endianness {
+            Endianness::Big => OAM::new(bytes),
+            Endianness::Little => OAM::new(&[bytes[1], bytes[0], bytes[3], bytes[2], bytes[5], bytes[4]])
         }
     }
 
+    pub fn from_hardware_oam(bytes: &[u8; 8]) -> OAM {
+        OAM::new_with_endianness(bytes, Endianness::Little)
+    }
+
+    // Editor .bin layout, NOT the hardware one: shape, size, flip, x, y, palette, tile upper byte, tile lower byte
+    // (see the .yan format at the top of export.rs)
     pub fn from_bin(bytes: &[u8]) -> OAM {
+        let shape_size_was_coerced = bytes[0] > 2 || bytes[1] > 3;
+
         let shape = match bytes[0] {
             0 => OAMShape::Square,
             1 => OAMShape::Horizontal,
@@ -117,7 +540,11 @@ impl OAM {
             _ => OAMSize::Size0,
         };
 
-        let flip = match bytes[2] {
+        // Bit 7 of the flip byte marks an X that didn't fit in the x byte, with bit 8 of the 9 bit X in bit 6.
+        // Older files never set it and store X as a plain i8
+        let wide_x = bytes[2] & 0x80 != 0;
+
+        let flip = match if wide_x { bytes[2] & 0x3F } else { bytes[2] } {
             0 => OAMFlip::None,
             1 => OAMFlip::Horizontal,
             2 => OAMFlip::Vertical,
@@ -125,14 +552,58 @@ impl OAM {
             _ => OAMFlip::None
         };
 
-        let x = bytes[3] as i8;
-        let y = bytes[4] as i8;
+        let x = if wide_x {
+            let x = ((((bytes[2] >> 6) & 1) as i16) << 8) | bytes[3] as i16;
+            if x >= 0x100 { x - 0x200 } else { x }
+        } else {
+            bytes[3] as i8 as i16
+        };
+        let y = bytes[4] as i8 as i16;
         let palette = bytes[5] as usize;
-        let tile = (((bytes[6] as usize) << 8) | (bytes[7] as usize)) as usize;
+        let tile = ((bytes[6] as usize) << 8) | (bytes[7] as usize);
 
-        OAM {shape, size, flip, x, y, palette, tile, selected: false, zindex: 0}
+        OAM {shape, size, flip, x, y, palette, tile, shape_size_was_coerced, ..Default::default()}
     }
     
+    // Copies with a single field changed, for building OAMs in iterator chains
+    pub fn with_tile(self, tile: usize) -> OAM {
+        OAM { tile, ..self }
+    }
+
+    pub fn with_palette(self, palette: usize) -> OAM {
+        OAM { palette, ..self }
+    }
+
+    pub fn with_position(self, x: i16, y: i16) -> OAM {
+        OAM { x, y, ..self }
+    }
+
+    pub fn with_flip(self, flip: OAMFlip) -> OAM {
+        OAM { flip, ..self }
+    }
+
+    // Only the fields that end up in saved data, selection and preview state are left out
+    pub fn hash_content<H: Hasher>(&self, hasher: &mut H) {
+        self.shape.hash(hasher);
+        self.size.hash(hasher);
+        self.flip.hash(hasher);
+        self.x.hash(hasher);
+        self.y.hash(hasher);
+        self.palette.hash(hasher);
+        self.tile.hash(hasher);
+        self.zindex.hash(hasher);
+        self.enabled.hash(hasher);
+        self.obj_mode.hash(hasher);
+        self.layer.hash(hasher);
+        
+        if let Some(affine) = self.affine {
+            affine.index.hash(hasher);
+            affine.scale_x.to_bits().hash(hasher);
+            affine.scale_y.to_bits().hash(hasher);
+            affine.double_size.hash(hasher);
+        }
+    }
+
     pub fn get_width_and_height(&self) -> (usize, usize) {
         match self.shape {
             OAMShape::Square => match self.size {
@@ -156,39 +627,62 @@ impl OAM {
         }
     }
 
-    pub fn get_sprite_indexes(&self) -> Vec<Vec<usize>> {
-        let mut sprite_indexes: Vec<Vec<usize>> = Vec::new();
-        
+    // Inverse of OAM::from_bin
+    pub fn to_bin(&self) -> [u8; 8] {
+        // X values that fit in an i8 are written the old way, so those files still load in older versions
+        let flip = if i8::try_from(self.x).is_ok() {
+            self.flip as u8
+        } else {
+            self.flip as u8 | 0x80 | ((((self.x as u16) >> 8) & 1) << 6) as u8
+        };
+
+        [
+            self.shape as u8,
+            self.size as u8,
+            flip,
+            self.x as u8,
+            self.y as u8,
+            self.palette as u8,
+            (self.tile >> 8) as u8,
+            (self.tile & 0xFF) as u8
+        ]
+    }
+
+    pub fn area_pixels(&self) -> usize {
         let (width, height) = self.get_width_and_height();
-        
-        let mut y_range: Vec<usize> = (0..height).collect();
-        let mut x_range: Vec<usize> = (0..width).collect();
-        
-        match self.flip {
-            OAMFlip::Horizontal => {
-                x_range = (0..width).rev().collect();
-            },
-            OAMFlip::Vertical => {
-                y_range = (0..height).rev().collect();
-            },
-            OAMFlip::Both => {
-                x_range = (0..width).rev().collect();
-                y_range = (0..height).rev().collect();
-            },
-            OAMFlip::None => {}
-        }
+        width * height * 64
+    }
 
-        for y in y_range {
-            let mut row: Vec<usize> = Vec::new();
-            
-            for &x in &x_range {
-                row.push(self.tile + x + y * 32);
-            }
+    // Tile shown at column x, row y of the OAM, computed directly so drawing doesn't have to allocate
+    pub fn get_sprite_index(&self, x: usize, y: usize) -> usize {
+        let (width, height) = self.get_width_and_height();
 
-            sprite_indexes.push(row);
-        }
+        let x = match self.flip {
+            OAMFlip::Horizontal | OAMFlip::Both => width - 1 - x,
+            _ => x
+        };
+
+        let y = match self.flip {
+            OAMFlip::Vertical | OAMFlip::Both => height - 1 - y,
+            _ => y
+        };
+
+        self.tile + x + y * 32
+    }
+
+    pub fn get_sprite_indexes(&self) -> Vec<Vec<usize>> {
+        let (width, height) = self.get_width_and_height();
         
-        return sprite_indexes;
+        (0..height)
+            .map(|y| (0..width).map(|x| self.get_sprite_index(x, y)).collect())
+            .collect()
+    }
+
+    // Byte offset of the base tile when tile counts whole tiles of the given depth, an 8x8 tile being
+    // 32 bytes at 4bpp and 64 at 8bpp. The attribute 2 tile number itself always counts 32 byte
+    // blocks on hardware, so for 8bpp sprites stored that way use bpp 4
+    pub fn tile_byte_offset(&self, bpp: u8) -> usize {
+        self.tile * 8 * bpp as usize
     }
 
     pub fn get_sprite_indexes_one_dimensional(&self) -> Vec<usize> {
@@ -201,170 +695,686 @@ impl OAM {
             }
         }
 
-        return indexes;
+        indexes
     }
 
-    pub fn draw(&self, textures: &Vec<Vec<TextureHandle>>, ui: &mut Ui, selection_indicator_enabled: bool) {
-        let oam_sprites = self.get_sprite_indexes();
-            
-        
+    // Area the OAM covers in the coordinate space it is drawn in
+    pub fn get_rect(&self) -> Rect {
+        let (width, height) = self.get_width_and_height();
+
+        // Double size affine sprites get a bounding box twice as big with the sprite centered in it
+        let box_scale = match self.affine {
+            Some(affine) if affine.double_size => 2.0,
+            _ => 1.0
+        };
+
+        Rect::from_min_size(
+            pos2((self.x as f32) * SPRITE_SIZE / 8.0, (self.y as f32) * SPRITE_SIZE / 8.0),
+            vec2(SPRITE_SIZE * width as f32, SPRITE_SIZE * height as f32) * box_scale
+        )
+    }
+
+    // Pulls the OAM back so its whole box is on the 240x160 display, returns whether it had to be moved
+    pub fn clamp_to_screen(&mut self) -> bool {
+        let size = self.get_rect().size() / (SPRITE_SIZE / 8.0);
+
+        let x = self.x.clamp(0, (SCREEN_WIDTH - size.x as i16).max(0));
+        let y = self.y.clamp(0, (SCREEN_HEIGHT - size.y as i16).max(0));
+        let clamped = x != self.x || y != self.y;
+
+        self.x = x;
+        self.y = y;
+
+        clamped
+    }
+
+    // Moves the OAM by a pointer delta in screen pixels, scale being the zoom the cel is shown at
+    pub fn apply_drag(&mut self, screen_delta: Vec2, scale: f32) {
+        let pixels = screen_delta / (SPRITE_SIZE / 8.0 * scale);
+
+        self.x = (self.x as f32 + pixels.x.round()).clamp(*OAM_X_RANGE.start() as f32, *OAM_X_RANGE.end() as f32) as i16;
+        self.y = (self.y as f32 + pixels.y.round()).clamp(*OAM_Y_RANGE.start() as f32, *OAM_Y_RANGE.end() as f32) as i16;
+    }
+
+    // Top left of the first tile and the size every tile is drawn at
+    fn tile_layout(&self) -> (Pos2, Vec2) {
+        let (width, height) = self.get_width_and_height();
+
+        // Affine sprites are scaled around the center of their bounding box
+        let scale = match self.affine {
+            Some(affine) => vec2(affine.scale_x, affine.scale_y),
+            None => vec2(1.0, 1.0)
+        };
+        let center = self.get_rect().center();
+        let origin = center - vec2(width as f32, height as f32) * SPRITE_SIZE * scale / 2.0;
+
+        (origin, vec2(SPRITE_SIZE, SPRITE_SIZE) * scale)
+    }
+
+    // get_sprite_index already mirrors which tile goes where, this mirrors the pixels inside each tile
+    fn flip_uv(&self) -> Rect {
+        match self.flip {
+            OAMFlip::None => Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+            OAMFlip::Horizontal => Rect::from_min_max(pos2(1.0, 0.0), pos2(0.0, 1.0)),
+            OAMFlip::Vertical => Rect::from_min_max(pos2(0.0, 1.0), pos2(1.0, 0.0)),
+            OAMFlip::Both => Rect::from_min_max(pos2(1.0, 1.0), pos2(0.0, 0.0))
+        }
+    }
+
+    // Returns the rect the sprite was painted over, or None if it isn't drawn (see is_drawn)
+    pub fn draw(&self, textures: &[Vec<TextureHandle>], ui: &mut Ui, options: DrawOptions) -> Option<Rect> {
+        if !self.is_drawn() {return None;}
+
         let (width, height) = self.get_width_and_height();
+        let (origin, tile_size) = self.tile_layout();
 
         for y in 0..height {
             for x in 0..width {
                 
-                let texture_sheet = match textures.get(self.palette) {
-                    Some(texture) => texture,
-                    None => continue
-                };
-
-                if oam_sprites[y][x] >= texture_sheet.len() {continue;}
-                
                 let rect = egui::Rect::from_min_size(
-                    pos2(
-                        (x as f32) * SPRITE_SIZE + (self.x as f32) * SPRITE_SIZE / 8.0, 
-                        (y as f32) * SPRITE_SIZE + (self.y as f32) * SPRITE_SIZE / 8.0),
-                    vec2(SPRITE_SIZE, SPRITE_SIZE)
+                    origin + vec2(x as f32, y as f32) * tile_size,
+                    tile_size
                 );
                 
-                let source = match texture_sheet.get(oam_sprites[y][x]) {
+                let source = match textures.get(self.palette).and_then(|sheet| sheet.get(self.get_sprite_index(x, y))) {
                     Some(source) => source,
-                    None => continue
+                    None => {
+                        if options.missing_placeholder {
+                            draw_missing_placeholder(ui, rect);
+                        }
+                        continue;
+                    }
                 };
 
                 ui.put(rect, |ui: &mut Ui| {
                     
-                    let mut texture = egui::Image::new(source);
-                    
-                    match self.flip { 
-                        OAMFlip::Horizontal => {
-                            texture = texture.uv(Rect::from_min_max(pos2(1.0, 0.0), pos2(0.0, 1.0)));
-                        },
-                        OAMFlip::Vertical => {
-                            texture = texture.uv(Rect::from_min_max(pos2(0.0, 1.0), pos2(1.0, 0.0)));
-                        },
-                        OAMFlip::Both => {
-                            texture = texture.uv(Rect::from_min_max(pos2(1.0, 1.0), pos2(0.0, 0.0)));
-                        },
-                        _ => {}
+                    let mut texture = egui::Image::new(source).uv(self.flip_uv());
+
+                    let mut tint = self.tint.unwrap_or(Color32::WHITE);
+
+                    if self.selected && options.selection_indicator {
+                        tint = tint * Color32::LIGHT_GREEN;
                     }
 
-                    if self.selected && selection_indicator_enabled {
-                        
-                        texture = texture.tint(Color32::LIGHT_GREEN);
+                    if let Some(opacity) = options.opacity {
+                        tint = tint.gamma_multiply(opacity.clamp(0.0, 1.0));
+                    }
+
+                    if tint != Color32::WHITE {
+                        texture = texture.tint(tint);
                     }
                     
                     ui.add(
-                        texture.fit_to_exact_size(vec2(SPRITE_SIZE, SPRITE_SIZE))
+                        texture.fit_to_exact_size(tile_size)
                     )
                 });
                 
                 //ui.allocate_space(vec2(SPRITE_SIZE, SPRITE_SIZE));
             }
         }
-        
-        
-        
-    }
-}
-
-#[derive(Deserialize, Serialize, Clone)]
-pub struct AnimationCel {
-    pub name: String,
-    pub oams: Vec<OAM>
-}
 
-fn parse_hex_string(string: &str) -> Option<u8> {
-    match u8::from_str_radix(&string, 16) {
-        Ok(value) => Some(value),
-        Err(_) => None
+        Some(Rect::from_min_size(origin, vec2(width as f32, height as f32) * tile_size))
     }
-}
 
-impl AnimationCel {
-    pub fn from_c(c: &str, name: &str) -> Option<AnimationCel> {
-        let oam_regex = Regex::new(r"0x[0-9a-fA-F]{4}").unwrap();
+    // Adds a quad per tile instead of painting it, positions being draw's scaled by scale then moved by offset. A tile
+    // goes into the last mesh when that uses the same texture and starts a new one otherwise, so painting the meshes
+    // in order stacks the tiles the same as draw
+    fn add_tiles_to_meshes(&self, textures: &[Vec<TextureHandle>], meshes: &mut Vec<Mesh>, offset: Vec2, scale: f32) {
+        if !self.is_drawn() {return;}
 
-        let words: Vec<_> = oam_regex.find_iter(c).map(|m| m.as_str()).collect();
-        let mut i = 0;
+        let sheet = match textures.get(self.palette) {
+            Some(sheet) => sheet,
+            None => return
+        };
 
-        let mut oams: Vec<OAM> = Vec::new();
-        let mut zindex = 0;
+        let (width, height) = self.get_width_and_height();
+        let (origin, tile_size) = self.tile_layout();
+        let origin = (origin.to_vec2() * scale + offset).to_pos2();
+        let tile_size = tile_size * scale;
+        let uv = self.flip_uv();
+        let tint = self.tint.unwrap_or(Color32::WHITE);
 
-        while i < words.len() {
-            let mut bytes: Vec<u8> = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(texture) = sheet.get(self.get_sprite_index(x, y)) {
+                    let rect = Rect::from_min_size(origin + vec2(x as f32, y as f32) * tile_size, tile_size);
+
+                    match meshes.last_mut() {
+                        Some(mesh) if mesh.texture_id == texture.id() => mesh.add_rect_with_uv(rect, uv, tint),
+                        _ => {
+                            let mut mesh = Mesh::with_texture(texture.id());
+                            mesh.add_rect_with_uv(rect, uv, tint);
+                            meshes.push(mesh);
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-            let word1 = words.get(i)?;
-            let word2 = words.get(i + 1)?;
-            let word3 = words.get(i + 2)?;
+    // Just the OAM's graphics as large as they fit centered in rect, for thumbnails. Position, affine scaling and
+    // enabled are ignored, flip, palette and tint aren't
+    pub fn draw_isolated(&self, textures: &[Vec<TextureHandle>], ui: &mut Ui, rect: Rect) {
+        let sheet = match textures.get(self.palette) {
+            Some(sheet) => sheet,
+            None => return
+        };
 
-            let byte1 = parse_hex_string(&word1[2..4])?;
-            let byte2 = parse_hex_string(&word1[4..6])?;
-            let byte3 = parse_hex_string(&word2[2..4])?;
-            let byte4 = parse_hex_string(&word2[4..6])?;
-            let byte5 = parse_hex_string(&word3[2..4])?;
-            let byte6 = parse_hex_string(&word3[4..6])?;
-            
-            bytes.push(byte1);
-            bytes.push(byte2);
-            bytes.push(byte3);
-            bytes.push(byte4);
-            bytes.push(byte5);
-            bytes.push(byte6);
-            
-            i += 3;
+        let (width, height) = self.get_width_and_height();
+        let tile_length = (rect.width() / width as f32).min(rect.height() / height as f32);
+        let tile_size = vec2(tile_length, tile_length);
+        let origin = rect.center() - vec2(width as f32, height as f32) * tile_length / 2.0;
 
-            let mut oam = OAM::new(&bytes);
-            oam.zindex = zindex;
+        let painter = ui.painter_at(rect);
+        let uv = self.flip_uv();
+        let tint = self.tint.unwrap_or(Color32::WHITE);
 
-            oams.push(oam);
-            
-            zindex += 1;
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(texture) = sheet.get(self.get_sprite_index(x, y)) {
+                    let tile_rect = Rect::from_min_size(origin + vec2(x as f32, y as f32) * tile_size, tile_size);
+                    painter.image(texture.id(), tile_rect, uv, tint);
+                }
+            }
         }
-
-        Some(AnimationCel { oams, name: name.to_string() })
     }
 
-    pub fn from_bin(bin: &[u8]) -> Option<AnimationCel> {
-        let mut name = String::from("");
-        let mut i = 0;
+    pub fn draw_coordinate_gizmo(&self, ui: &mut Ui) {
+        let origin = self.get_rect().min;
+        let stroke = Stroke::new(1.0, Color32::YELLOW);
 
-        while bin[i] != 0x00 {
-            name.push(bin[i] as char);
-            i += 1;
-        }
+        ui.painter().line_segment([origin - vec2(4.0, 0.0), origin + vec2(4.0, 0.0)], stroke);
+        ui.painter().line_segment([origin - vec2(0.0, 4.0), origin + vec2(0.0, 4.0)], stroke);
 
-        i += 1;
+        ui.painter().text(
+            origin + vec2(2.0, 2.0),
+            Align2::LEFT_TOP,
+            format!("({}, {})", self.x, self.y),
+            FontId::monospace(6.0),
+            Color32::YELLOW
+        );
+    }
+}
 
-        let length = bin[i] as usize;
+// Which position in each source triple holds attr0, attr1 and attr2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordOrder {
+    positions: [usize; 3]
+}
+
+impl WordOrder {
+    // None unless the positions are 0, 1 and 2 in some order
+    pub fn new(attr0: usize, attr1: usize, attr2: usize) -> Option<WordOrder> {
+        let mut sorted = [attr0, attr1, attr2];
+        sorted.sort();
+
+        if sorted != [0, 1, 2] {
+            return None;
+        }
+
+        Some(WordOrder { positions: [attr0, attr1, attr2] })
+    }
+}
+
+impl Default for WordOrder {
+    fn default() -> Self {
+        WordOrder { positions: [0, 1, 2] }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct AnimationCel {
+    pub name: String,
+    pub oams: Vec<OAM>
+}
+
+// Frame durations are a u16, anything outside that is a bug somewhere upstream
+// so it gets logged as a warning instead of silently wrapping
+fn clamp_duration(ticks: isize, context: &str) -> u16 {
+    if ticks < 0 || ticks > u16::MAX as isize {
+        log::warn!("{} produced a frame duration of {} ticks, clamping to 0..={}", context, ticks, u16::MAX);
+    }
+
+    ticks.clamp(0, u16::MAX as isize) as u16
+}
+
+// Scales both corners from the origin, unlike Rect::scale_from_center
+fn scale_rect(rect: Rect, scale: f32) -> Rect {
+    Rect::from_min_max((rect.min.to_vec2() * scale).to_pos2(), (rect.max.to_vec2() * scale).to_pos2())
+}
+
+fn parse_hex_string(string: &str) -> Option<u8> {
+    u8::from_str_radix(string, 16).ok()
+}
+
+impl AnimationCel {
+    // note is handed a description of every decoded OAM and of the triple that stopped the parse
+    fn parse_c(c: &str, name: &str, order: WordOrder, mut note: impl FnMut(String)) -> Result<AnimationCel, ParseError> {
+        let oam_regex = Regex::new(r"0x[0-9a-fA-F]{4}").unwrap();
+
+        let words: Vec<_> = oam_regex.find_iter(c).collect();
+        let mut i = 0;
+
+        if words.is_empty() {
+            note("no 0xXXXX words in the input".to_string());
+            return Err(ParseError::NoWords);
+        }
+
+        let mut oams: Vec<OAM> = Vec::new();
+        let mut zindex = 0;
+
+        while i < words.len() {
+            let triple: Vec<&str> = words[i..words.len().min(i + 3)].iter().map(|word| word.as_str()).collect();
+
+            if triple.len() < 3 {
+                note(format!("OAM {}: incomplete triple {} at offset {}", oams.len(), triple.join(", "), words[i].start()));
+                return Err(ParseError::Truncated);
+            }
+
+            let mut bytes: Vec<u8> = Vec::new();
+
+            for word in &words[i..i + 3] {
+                let invalid_word = ParseError::InvalidWord { offset: word.start() };
+                
+                let parsed = parse_hex_string(&word.as_str()[2..4]).zip(parse_hex_string(&word.as_str()[4..6]));
+                let (high, low) = match parsed {
+                    Some(parsed) => parsed,
+                    None => {
+                        note(format!("OAM {}: bad word {} in triple {}", oams.len(), word.as_str(), triple.join(", ")));
+                        return Err(invalid_word);
+                    }
+                };
+
+                bytes.push(high);
+                bytes.push(low);
+            }
+            
+            i += 3;
+
+            let bytes: Vec<u8> = order.positions.iter()
+                .flat_map(|&position| [bytes[position * 2], bytes[position * 2 + 1]])
+                .collect();
+
+            let mut oam = OAM::new(&bytes);
+            oam.zindex = zindex;
+
+            note(format!(
+                "OAM {}: {} -> {:?} {:?} flip {:?} at ({}, {}) palette {} tile {}",
+                oams.len(), triple.join(", "), oam.shape, oam.size, oam.flip, oam.x, oam.y, oam.palette, oam.tile
+            ));
+
+            oams.push(oam);
+            
+            zindex += 1;
+        }
+
+        Ok(AnimationCel { oams, name: name.to_string() })
+    }
+
+    pub fn from_c(c: &str, name: &str) -> Result<AnimationCel, ParseError> {
+        AnimationCel::parse_c(c, name, WordOrder::default(), |_| {})
+    }
+
+    // For dumps that don't store the attributes in attr0, attr1, attr2 order
+    pub fn from_c_with_order(c: &str, name: &str, order: WordOrder) -> Result<AnimationCel, ParseError> {
+        AnimationCel::parse_c(c, name, order, |_| {})
+    }
+
+    // Same as from_c, but also returns a line per parsed OAM for working out unfamiliar layouts
+    pub fn from_c_verbose(c: &str, name: &str) -> (Option<AnimationCel>, Vec<String>) {
+        let mut notes = Vec::new();
+        let result = AnimationCel::parse_c(c, name, WordOrder::default(), |line| notes.push(line));
+
+        match result {
+            Ok(cel) => (Some(cel), notes),
+            Err(error) => {
+                notes.push(format!("stopped: {}", error));
+                (None, notes)
+            }
+        }
+    }
+
+    pub fn from_c_opt(c: &str, name: &str) -> Option<AnimationCel> {
+        AnimationCel::from_c(c, name).ok()
+    }
+
+    pub fn from_bin(bin: &[u8]) -> Result<AnimationCel, ParseError> {
+        let mut i = 0;
+
+        // Anything other than a short printable name means the data is misaligned. Bytes past ASCII are
+        // let through since they're part of a multi-byte UTF-8 character
+        while *bin.get(i).ok_or(ParseError::MissingTerminator)? != 0x00 {
+            if i >= MAX_NAME_LENGTH || (bin[i].is_ascii() && !bin[i].is_ascii_graphic()) {
+                return Err(ParseError::InvalidName);
+            }
+
+            i += 1;
+        }
+
+        if i == 0 {
+            return Err(ParseError::InvalidName);
+        }
+
+        let name = decode_name(&bin[..i]);
+
+        i += 1;
+
+        let length = *bin.get(i).ok_or(ParseError::Truncated)? as usize;
         let mut oams = Vec::new();
         i += 1;
         for x in 0..length {
-            oams.push(OAM::from_bin(&bin[i + (x * 8)..i + (x * 8) + 8]))
+            oams.push(OAM::from_bin(bin.get(i + (x * 8)..i + (x * 8) + 8).ok_or(ParseError::Truncated)?))
         }
         
 
-        Some(AnimationCel { name, oams })
+        Ok(AnimationCel { name, oams })
+    }
+
+    pub fn from_bin_opt(bin: &[u8]) -> Option<AnimationCel> {
+        AnimationCel::from_bin(bin).ok()
+    }
+
+    // Parses bin and writes it back out with to_bin, giving the offset of the first byte that came out different.
+    // Only the bytes from_bin reads are compared, anything after the last OAM is ignored. Expected differences:
+    // unknown shape, size or flip values are written back as the default they were read as, a wide X that fits
+    // in a signed byte comes back in the short form, and invalid UTF-8 in the name becomes U+FFFD
+    pub fn bin_roundtrip_mismatch(bin: &[u8]) -> Result<Option<usize>, ParseError> {
+        let written = AnimationCel::from_bin(bin)?.to_bin().unwrap_or_default();
+        let read = bin.len().min(written.len());
+
+        Ok(first_mismatch(&bin[..read], &written))
+    }
+
+    // Reads exactly one cel, so concatenated cels can be read one after another from the same reader
+    pub fn from_bin_reader<R: Read>(reader: &mut R) -> Result<AnimationCel, ParseError> {
+        let mut bytes = Vec::new();
+
+        read_name_bytes(reader, &mut bytes)?;
+        read_bytes(reader, &mut bytes, 1)?;
+
+        let length = bytes[bytes.len() - 1] as usize;
+        read_bytes(reader, &mut bytes, length * 8)?;
+
+        AnimationCel::from_bin(&bytes)
+    }
+
+    // Inverse of AnimationCel::from_bin. Anything from_bin couldn't read back is an error rather than being truncated
+    pub fn to_bin(&self) -> Result<Vec<u8>, WriteError> {
+        if self.name.len() > MAX_NAME_LENGTH {
+            return Err(WriteError::NameTooLong { length: self.name.len() });
+        }
+
+        let count = u8::try_from(self.oams.len()).map_err(|_| WriteError::TooManyOAMs { count: self.oams.len() })?;
+
+        if let Some(oam) = self.oams.iter().find(|oam| u8::try_from(oam.palette).is_err()) {
+            return Err(WriteError::PaletteOutOfRange { palette: oam.palette });
+        }
+
+        let mut bytes = Vec::new();
+
+        bytes.extend(self.name.as_bytes());
+        bytes.push(0x00);
+        bytes.push(count);
+
+        for oam in &self.oams {
+            bytes.extend(oam.to_bin());
+        }
+
+        Ok(bytes)
+    }
+
+    // Same bytes as to_bin, for .incbin-ing straight into an assembly project
+    pub fn to_asm_bytes(&self) -> Result<Vec<u8>, WriteError> {
+        self.to_bin()
+    }
+
+    pub fn to_asm_string(&self, label: &str) -> Result<String, WriteError> {
+        let mut asm = format!("{}:\n", label);
+
+        for line in self.to_asm_bytes()?.chunks(16) {
+            let bytes: Vec<String> = line.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+            asm.push_str(&format!("    .byte {}\n", bytes.join(", ")));
+        }
+
+        Ok(asm)
+    }
+
+    // OAM indexes sorted by zindex, lowest zindex first. OAMs are drawn in reverse of this order
+    // so the lowest zindex ends up on top
+    pub fn draw_order_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.oams.len()).collect();
+        indices.sort_by_key(|&i| self.oams[i].zindex);
+
+        indices
+    }
+
+    // Owned copy in hardware order (topmost first, like draw_order_indices) with the editor only state cleared
+    pub fn to_render_list(&self) -> Vec<OAM> {
+        self.draw_order_indices()
+            .into_iter()
+            .map(|i| OAM { selected: false, tint: None, ..self.oams[i].clone() })
+            .collect()
     }
 
-    pub fn draw(&self, textures: &Vec<Vec<TextureHandle>>, ui: &mut Ui, selection_indicator_enabled: bool) {
-        let mut sorted_oams = self.oams.clone();
-        sorted_oams.sort_by(|a, b| a.zindex.cmp(&b.zindex));
+    // One single OAM cel per OAM, named <cel>_<index>
+    pub fn explode(&self) -> Vec<AnimationCel> {
+        self.oams.iter().enumerate().map(|(i, oam)| AnimationCel {
+            name: format!("{}_{}", self.name, i),
+            oams: vec![OAM { selected: false, zindex: 0, ..oam.clone() }]
+        }).collect()
+    }
+
+    // Names of every layer used in the cel, in the order they first show up
+    pub fn layers(&self) -> Vec<String> {
+        let mut layers: Vec<String> = Vec::new();
+
+        for layer in self.oams.iter().filter_map(|oam| oam.layer.as_ref()) {
+            if !layers.contains(layer) {
+                layers.push(layer.clone());
+            }
+        }
+
+        layers
+    }
+
+    // Moves every OAM in the layer by the offset, clamped to the OAM coordinate range
+    pub fn translate_layer(&mut self, layer: &str, offset: (i16, i16)) {
+        for oam in self.oams.iter_mut().filter(|oam| oam.layer.as_deref() == Some(layer)) {
+            oam.x = oam.x.saturating_add(offset.0).clamp(*OAM_X_RANGE.start(), *OAM_X_RANGE.end());
+            oam.y = oam.y.saturating_add(offset.1).clamp(*OAM_Y_RANGE.start(), *OAM_Y_RANGE.end());
+        }
+    }
+
+    pub fn set_layer_visible(&mut self, layer: &str, visible: bool) {
+        for oam in self.oams.iter_mut().filter(|oam| oam.layer.as_deref() == Some(layer)) {
+            oam.hidden = !visible;
+        }
+    }
+
+    // Mirrors the whole cel around the vertical line at center_x, toggling each sprite's horizontal flip and moving
+    // it so its right edge lands where its left edge was reflected to. Affine OAMs have no flip bits, so they
+    // only get moved and their matrix has to be mirrored in the parameter table
+    pub fn mirror_horizontal(&mut self, center_x: i16) {
+        for oam in &mut self.oams {
+            let width = (oam.get_rect().width() / (SPRITE_SIZE / 8.0)) as i32;
+            let x = 2 * center_x as i32 - oam.x as i32 - width;
+
+            oam.x = x.clamp(*OAM_X_RANGE.start() as i32, *OAM_X_RANGE.end() as i32) as i16;
+
+            if oam.affine.is_some() {continue;}
+
+            oam.flip = match oam.flip {
+                OAMFlip::None => OAMFlip::Horizontal,
+                OAMFlip::Horizontal => OAMFlip::None,
+                OAMFlip::Vertical => OAMFlip::Both,
+                OAMFlip::Both => OAMFlip::Vertical
+            };
+        }
+    }
+
+    // Positions linearly interpolated from self (t = 0) to other (t = 1), every other field and the name come
+    // from self. OAMs are paired up by index, so both cels need the same number of OAMs
+    pub fn tween(&self, other: &AnimationCel, t: f32) -> Option<AnimationCel> {
+        if self.oams.len() != other.oams.len() {return None;}
+
+        let lerp = |a: i16, b: i16, range: &RangeInclusive<i16>| {
+            (a as f32 + (b as f32 - a as f32) * t).round().clamp(*range.start() as f32, *range.end() as f32) as i16
+        };
+
+        let oams = self.oams.iter().zip(&other.oams).map(|(a, b)| {
+            a.clone().with_position(lerp(a.x, b.x, &OAM_X_RANGE), lerp(a.y, b.y, &OAM_Y_RANGE))
+        }).collect();
+
+        Some(AnimationCel { name: self.name.clone(), oams })
+    }
+
+    // Topmost shown OAM under point. Like every hit test here, point is in draw's coordinates multiplied by scale,
+    // the same space screen_bounds returns
+    pub fn oam_at(&self, point: Pos2, scale: f32) -> Option<usize> {
+        self.draw_order_indices()
+            .into_iter()
+            .find(|&i| self.oams[i].is_drawn() && scale_rect(self.oams[i].get_rect(), scale).contains(point))
+    }
+
+    // Shown OAMs whose rect touches the marquee, rect being in draw's coordinates multiplied by scale like oam_at
+    pub fn oams_in_rect(&self, rect: Rect, scale: f32) -> Vec<usize> {
+        self.oams.iter().enumerate()
+            .filter(|(_, oam)| oam.is_drawn() && scale_rect(oam.get_rect(), scale).intersects(rect))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Box selection: selects every OAM in the marquee, with additive the existing selection is kept
+    pub fn select_in_rect(&mut self, rect: Rect, scale: f32, additive: bool) -> Vec<usize> {
+        let hits = self.oams_in_rect(rect, scale);
+
+        for (i, oam) in self.oams.iter_mut().enumerate() {
+            if hits.contains(&i) {
+                oam.selected = true;
+            } else if !additive {
+                oam.selected = false;
+            }
+        }
+
+        hits
+    }
+
+    // Click selection: toggles the topmost OAM under point and deselects the rest,
+    // with additive (shift-click) the other selections are kept. Returns the OAM that was hit
+    pub fn toggle_select_at(&mut self, point: Pos2, scale: f32, additive: bool) -> Option<usize> {
+        let hit = self.oam_at(point, scale);
+
+        for (i, oam) in self.oams.iter_mut().enumerate() {
+            if Some(i) == hit {
+                oam.selected = !oam.selected;
+            } else if !additive {
+                oam.selected = false;
+            }
+        }
+
+        hit
+    }
+
+    // Selected OAMs topmost first, with the selection and zindex cleared so they can go into any cel
+    pub fn copy_selected(&self) -> Vec<OAM> {
+        self.draw_order_indices()
+            .into_iter()
+            .filter(|&i| self.oams[i].selected)
+            .map(|i| OAM { selected: false, zindex: 0, ..self.oams[i].clone() })
+            .collect()
+    }
+
+    // Puts the OAMs above everything already in the cel, keeping their order, moved by offset pixels
+    pub fn paste_oams(&mut self, oams: &[OAM], offset: (i16, i16)) {
+        for oam in &mut self.oams {
+            oam.zindex += oams.len();
+        }
+
+        for (i, oam) in oams.iter().enumerate() {
+            let mut oam = oam.clone();
+
+            oam.x = oam.x.saturating_add(offset.0).clamp(*OAM_X_RANGE.start(), *OAM_X_RANGE.end());
+            oam.y = oam.y.saturating_add(offset.1).clamp(*OAM_Y_RANGE.start(), *OAM_Y_RANGE.end());
+            oam.zindex = i;
+
+            self.oams.push(oam);
+        }
+    }
+
+    // Every referenced tile is treated as opaque, so this only reports OAMs that are certainly hidden
+    pub fn fully_occluded_oams(&self) -> Vec<usize> {
+        let mut covered: HashSet<(i32, i32)> = HashSet::new();
+        let mut occluded = Vec::new();
+
+        // draw_order_indices goes from the topmost OAM down
+        for i in self.draw_order_indices() {
+            let oam = &self.oams[i];
+            if !oam.is_drawn() {continue;}
+
+            let (width, height) = oam.get_width_and_height();
+            let pixels: Vec<(i32, i32)> = (0..height as i32 * 8)
+                .flat_map(|y| (0..width as i32 * 8).map(move |x| (oam.x as i32 + x, oam.y as i32 + y)))
+                .collect();
+
+            if pixels.iter().all(|pixel| covered.contains(pixel)) {
+                occluded.push(i);
+            }
+
+            covered.extend(pixels);
+        }
+
+        occluded.sort();
+        occluded
+    }
+
+    pub fn draw(&self, textures: &[Vec<TextureHandle>], ui: &mut Ui, options: DrawOptions) {
+        self.draw_with_rects(textures, ui, options);
+    }
+
+    // Same as draw, also returning (OAM index, painted rect) for every OAM that was drawn, bottom to top
+    pub fn draw_with_rects(&self, textures: &[Vec<TextureHandle>], ui: &mut Ui, options: DrawOptions) -> Vec<(usize, Rect)> {
+        self.draw_within(textures, ui, options, Rect::EVERYTHING)
+    }
+
+    // Only draws the OAMs whose rect intersects clip, for zoomed in views of big cels. clip is in draw's coordinates
+    pub fn draw_clipped(&self, textures: &[Vec<TextureHandle>], ui: &mut Ui, clip: Rect, options: DrawOptions) -> Vec<(usize, Rect)> {
+        self.draw_within(textures, ui, options, clip)
+    }
+
+    fn draw_within(&self, textures: &[Vec<TextureHandle>], ui: &mut Ui, options: DrawOptions, clip: Rect) -> Vec<(usize, Rect)> {
+        let draw_order = self.draw_order_indices();
         
         let mut selected_oam = None;
+        let mut rects = Vec::new();
+
+        // Stroked first so it sits behind the sprites
+        if options.safe_area {
+            let pixel = SPRITE_SIZE / 8.0;
+            
+            ui.painter().rect_stroke(
+                Rect::from_min_size(Pos2::ZERO, vec2(SCREEN_WIDTH as f32 * pixel, SCREEN_HEIGHT as f32 * pixel)),
+                0,
+                Stroke::new(1.0, Color32::LIGHT_GREEN),
+                egui::StrokeKind::Outside
+            );
+        }
 
-        for oam in sorted_oams.iter().rev() {
-            oam.draw(textures, ui, selection_indicator_enabled);
+        for &i in draw_order.iter().rev() {
+            let oam = &self.oams[i];
+            if !oam.is_drawn() || !oam.get_rect().intersects(clip) {continue;}
+
+            if let Some(rect) = oam.draw(textures, ui, options) {
+                rects.push((i, rect));
+            }
             if oam.selected {
                 selected_oam = Some(oam);
             }
         }
         
         if let Some(selected_oam) = selected_oam {
-            if selection_indicator_enabled {
-                let (width, height) = selected_oam.get_width_and_height();
+            if options.selection_indicator {
                 ui.painter().rect_stroke(
-                    Rect::from_min_size(pos2((selected_oam.x as f32) * SPRITE_SIZE / 8.0, (selected_oam.y as f32) * SPRITE_SIZE / 8.0), vec2(SPRITE_SIZE * width as f32, SPRITE_SIZE * height as f32)), 
+                    selected_oam.get_rect(), 
                     0, 
                     Stroke::new(2.0, Color32::RED), 
                     egui::StrokeKind::Outside
@@ -372,169 +1382,915 @@ impl AnimationCel {
             }
         }
 
+        if options.coordinate_gizmos {
+            for oam in self.oams.iter().filter(|oam| oam.is_drawn() && oam.get_rect().intersects(clip)) {
+                oam.draw_coordinate_gizmo(ui);
+            }
+        }
+
+        // Painted after every sprite so a label is never covered by the OAMs above it
+        if options.debug_labels {
+            for &(i, rect) in &rects {
+                ui.painter().text(
+                    rect.right_bottom() - vec2(1.0, 1.0),
+                    Align2::RIGHT_BOTTOM,
+                    format!("#{} z{}", i, self.oams[i].zindex),
+                    FontId::monospace(6.0),
+                    Color32::WHITE
+                );
+            }
+        }
+
+        rects
+    }
+
+    // draw with every OAM's tint faded towards transparent, opacity going from 0 (invisible) to 1 (same as draw)
+    pub fn draw_with_opacity(&self, textures: &[Vec<TextureHandle>], ui: &mut Ui, options: DrawOptions, opacity: f32) {
+        self.draw(textures, ui, DrawOptions { opacity: Some(opacity), ..options });
+    }
+
+    // For previewing many cels at once, e.g. the thumbnails in the cel list. Each cel is fit centered in its rect.
+    // Runs of tiles sharing a texture become one mesh, skipping the child ui draw puts around every tile, and the
+    // meshes are painted in draw order. Skips the selection indicator, gizmos and placeholders
+    pub fn draw_batched(cels: &[(&AnimationCel, Rect)], textures: &[Vec<TextureHandle>], ui: &mut Ui) {
+        let mut meshes = Vec::new();
+
+        for (cel, rect) in cels {
+            let bounds = cel.screen_bounds(1.0);
+            if bounds.width() <= 0.0 || bounds.height() <= 0.0 {continue;}
+
+            let scale = (rect.width() / bounds.width()).min(rect.height() / bounds.height());
+            let offset = rect.center().to_vec2() - bounds.center().to_vec2() * scale;
+
+            for &i in cel.draw_order_indices().iter().rev() {
+                cel.oams[i].add_tiles_to_meshes(textures, &mut meshes, offset, scale);
+            }
+        }
+
+        let painter = ui.painter();
+
+        for mesh in meshes {
+            painter.add(mesh);
+        }
+    }
+
+    // Area draw covers, in draw's coordinates multiplied by scale. Negative OAM positions put min above/left of
+    // the origin, so lay previews out by the size and shift them by -min. Rect::ZERO when nothing is drawn
+    pub fn screen_bounds(&self, scale: f32) -> Rect {
+        let bounds = self.oams.iter()
+            .filter(|oam| oam.is_drawn())
+            .map(|oam| oam.get_rect())
+            .reduce(|a, b| a.union(b));
+
+        match bounds {
+            Some(bounds) => scale_rect(bounds, scale),
+            None => Rect::ZERO
+        }
+    }
+
+    // Pixel area covered by all OAMs as (min x, min y, max x, max y), max is exclusive
+    pub fn get_pixel_bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+
+        for oam in &self.oams {
+            let (width, height) = oam.get_width_and_height();
+            let (min_x, min_y) = (oam.x as i32, oam.y as i32);
+            let (max_x, max_y) = (min_x + width as i32 * 8, min_y + height as i32 * 8);
+
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(min_x), y0.min(min_y), x1.max(max_x), y1.max(max_y)),
+                None => (min_x, min_y, max_x, max_y)
+            });
+        }
+
+        bounds
+    }
+
+    // Total pixels covered by OAMs (overlapping OAMs are counted twice) and the area of their bounding box
+    pub fn coverage(&self) -> (usize, usize) {
+        let covered = self.oams.iter().map(|oam| oam.area_pixels()).sum();
+
+        let bounding_box = match self.get_pixel_bounds() {
+            Some((min_x, min_y, max_x, max_y)) => ((max_x - min_x) * (max_y - min_y)) as usize,
+            None => 0
+        };
+
+        (covered, bounding_box)
+    }
+
+    // Headless version of draw, works off the spritesheet and palette data since textures live on the GPU.
+    // The cel origin is placed at the origin pixel of the image and every pixel is scaled up by scale
+    pub fn render_to_image(&self, image: &mut RgbaImage, spritesheet: &Spritesheet, palette: &Palette, origin: (i32, i32), scale: u32) {
+        for &i in self.draw_order_indices().iter().rev() {
+            let oam = &self.oams[i];
+
+            if !oam.is_drawn() { continue; }
+
+            let colors = match palette.palettes.get(oam.palette) {
+                Some(colors) => colors,
+                None => continue
+            };
+
+            let flip_x = oam.flip == OAMFlip::Horizontal || oam.flip == OAMFlip::Both;
+            let flip_y = oam.flip == OAMFlip::Vertical || oam.flip == OAMFlip::Both;
+
+            let (width, height) = oam.get_width_and_height();
+
+            for tile_y in 0..height {
+                for tile_x in 0..width {
+                    let sprite = match spritesheet.sprites.get(oam.get_sprite_index(tile_x, tile_y)) {
+                        Some(sprite) => sprite,
+                        None => continue
+                    };
+
+                    for pixel in 0..0x40 {
+                        let (mut px, mut py) = (pixel % 8, pixel / 8);
+                        
+                        if flip_x { px = 7 - px; }
+                        if flip_y { py = 7 - py; }
+
+                        let palette_id = sprite.pixels[py * 8 + px] as usize;
+                        if palette_id == 0 { continue; }
+
+                        let rgb = match colors.get(palette_id) {
+                            Some(rgb) => rgb,
+                            None => continue
+                        };
+
+                        let x = origin.0 + oam.x as i32 + (tile_x * 8 + pixel % 8) as i32;
+                        let y = origin.1 + oam.y as i32 + (tile_y * 8 + pixel / 8) as i32;
+
+                        for sy in 0..scale {
+                            for sx in 0..scale {
+                                let (image_x, image_y) = (x * scale as i32 + sx as i32, y * scale as i32 + sy as i32);
+                                
+                                if image_x < 0 || image_y < 0 || image_x >= image.width() as i32 || image_y >= image.height() as i32 {
+                                    continue;
+                                }
+
+                                image.put_pixel(image_x as u32, image_y as u32, Rgba([rgb.r, rgb.g, rgb.b, 255]));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+
+        self.name.hash(&mut hasher);
+        self.oams.len().hash(&mut hasher);
+
+        for oam in &self.oams {
+            oam.hash_content(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    // Like content_hash but without the name, so identical cels saved under different names hash the same
+    pub fn oams_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+
+        self.oams.len().hash(&mut hasher);
+
+        for oam in &self.oams {
+            oam.hash_content(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    pub fn get_used_tiles(&self) -> HashSet<usize> {
+        let mut used_tiles = HashSet::new();
+
+        for oam in &self.oams {
+            used_tiles.extend(oam.get_sprite_indexes_one_dimensional());
+        }
+
+        used_tiles
+    }
+
+    // Indexes of the OAMs that show the tile anywhere in their block, not just as their base tile
+    pub fn oams_using_tile(&self, tile: usize) -> Vec<usize> {
+        self.oams.iter().enumerate()
+            .filter(|(_, oam)| oam.get_sprite_indexes_one_dimensional().contains(&tile))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Lowest and highest tile index used by any OAM
+    pub fn tile_range(&self) -> Option<(usize, usize)> {
+        let used_tiles = self.get_used_tiles();
         
+        Some((*used_tiles.iter().min()?, *used_tiles.iter().max()?))
+    }
+
+    // Affine parameter slots referenced by rotation/scaling OAMs, sorted and without repeats
+    pub fn affine_indices_used(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self.oams.iter()
+            .filter_map(|oam| oam.affine)
+            .map(|affine| affine.index as usize)
+            .collect();
+
+        indices.sort_unstable();
+        indices.dedup();
+
+        indices
+    }
+
+    // Shifts the tiles of only the OAMs on one palette. Nothing changes and false is returned when any of
+    // them would end up outside 0..=MAX_TILE
+    pub fn offset_tiles_for_palette(&mut self, palette: usize, delta: isize) -> bool {
+        let mut tiles = Vec::new();
+
+        for oam in self.oams.iter().filter(|oam| oam.palette == palette) {
+            match oam.tile.checked_add_signed(delta) {
+                Some(tile) if tile <= MAX_TILE => tiles.push(tile),
+                _ => return false
+            }
+        }
+
+        for (oam, tile) in self.oams.iter_mut().filter(|oam| oam.palette == palette).zip(tiles) {
+            oam.tile = tile;
+        }
+
+        true
+    }
+
+    // Palette used by the most OAMs, ties go to the lowest palette index
+    pub fn dominant_palette(&self) -> Option<usize> {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+
+        for oam in &self.oams {
+            *counts.entry(oam.palette).or_insert(0) += 1;
+        }
+
+        counts
+            .into_iter()
+            .max_by_key(|&(palette, count)| (count, std::cmp::Reverse(palette)))
+            .map(|(palette, _)| palette)
     }
 }
 
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationBinHeader {
+    pub name: String,
+    // Bytes of frame records after the header, to_bin always writes the real length here
+    pub frames_length: usize,
+    // Bytes up to the first frame record: name, separator and the 2 length bytes
+    pub header_length: usize
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct AnimationFrame {
     pub cell: String,
-    pub duration: u8,
+    pub duration: u16,
     #[serde(skip)]
-    pub id: usize
+    pub id: usize,
+    // Playback stops on the last tick of this frame until release_hold is called. Only kept in the JSON project
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub hold: bool
 }
 
 pub struct PositionedAnimationFrame {
     cell: String,
     pub position: isize,
-    id: usize
+    id: usize,
+    hold: bool
+}
+
+// Flattened, render ready form of an animation for game engines: every frame has its OAMs
+// resolved from the cel and listed topmost first, width and height are in pixels
+#[derive(Debug, Clone, Serialize)]
+pub struct MetaspriteEntry {
+    pub tile: usize,
+    pub palette: usize,
+    pub x: i16,
+    pub y: i16,
+    pub flip: OAMFlip,
+    pub width: usize,
+    pub height: usize,
+    pub obj_mode: ObjMode,
+    // Matrix index and scale of rotation/scaling sprites. With double_size the sprite is centered in a box
+    // twice its width and height
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub affine: Option<OAMAffine>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetaspriteFrame {
+    pub duration: u16,
+    pub entries: Vec<MetaspriteEntry>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetaspriteAnimation {
+    pub name: String,
+    pub frames: Vec<MetaspriteFrame>
+}
+
+// JSON side of export_atlas, every rect is in atlas pixels
+#[derive(Debug, Clone, Serialize)]
+pub struct AtlasRegion {
+    pub cel: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AtlasFrame {
+    // Index into regions
+    pub region: usize,
+    pub duration: u16
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AtlasExport {
+    pub name: String,
+    pub image: String,
+    // Where the cel origin sits inside every region
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub regions: Vec<AtlasRegion>,
+    pub frames: Vec<AtlasFrame>
+}
+
+// The parts of an Aseprite JSON export that from_aseprite_json reads
+#[derive(Deserialize)]
+struct AsepriteExport {
+    frames: AsepriteFrames,
+    meta: AsepriteMeta
+}
+
+#[derive(Deserialize)]
+struct AsepriteFrame {
+    // Milliseconds
+    duration: f32
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AsepriteMeta {
+    #[serde(default)]
+    frame_tags: Vec<AsepriteTag>
+}
+
+#[derive(Deserialize)]
+struct AsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String
+}
+
+// Aseprite writes frames either as an array or as an object keyed by file name, both in frame order.
+// Going through a visitor keeps that order for the object form, which a HashMap would lose
+struct AsepriteFrames(Vec<AsepriteFrame>);
+
+impl<'de> Deserialize<'de> for AsepriteFrames {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for FramesVisitor {
+            type Value = AsepriteFrames;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "an array or object of frames")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element()? {
+                    frames.push(frame);
+                }
+
+                Ok(AsepriteFrames(frames))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some((_, frame)) = map.next_entry::<String, AsepriteFrame>()? {
+                    frames.push(frame);
+                }
+
+                Ok(AsepriteFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+// One frame laid out on the timeline, start and width are in ticks
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineSegment {
+    pub start: usize,
+    pub width: usize,
+    pub cel: String,
+    pub id: usize,
+    pub selected: bool
 }
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Animation {
     pub frames: Vec<AnimationFrame>,
     pub name: String,
+    // Ticks per second the animation is meant to play at, None being the usual 60
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f32>,
     #[serde(skip)]
     pub current_frame: usize,
     #[serde(skip)]
-    pub duration: usize
+    pub duration: usize,
+    // Playhead used by the advance helpers
+    #[serde(skip)]
+    pub current_tick: usize,
+    // Preview playback rate, scales the ticks given to advance_with_callback without touching the durations
+    #[serde(skip, default = "speed_default")]
+    pub speed: f32,
+    // Fraction of a tick left over from the last advance at the current speed
+    #[serde(skip)]
+    tick_remainder: f32,
+    // Set by release_hold, lets the playhead past the next held frame it's stopped on
+    #[serde(skip)]
+    hold_released: bool,
+    // Tick every frame ends on, built by the first lookup after the frames change. The methods here that edit
+    // frames clear it, code editing frames directly has to call frames_changed
+    #[serde(skip)]
+    frame_ends: OnceLock<Vec<usize>>
+}
+
+fn speed_default() -> f32 {
+    1.0
 }
 
 impl Animation {
-    pub fn from_c(c: &str, name: &str) -> Option<Animation> {
+    // Splits a C animation table into its raw (cel reference, duration) entries
+    fn parse_c_entries(c: &str) -> Result<Vec<(String, u16)>, ParseError> {
+        // Collected once so indexing is O(1), chars().nth() has to walk the whole string every time
+        let chars: Vec<char> = c.chars().collect();
+
+        // The first { is the array itself and every { directly inside it starts a frame entry,
+        // so entries are picked out by nesting depth instead of position
+        let mut depth = 0;
+        let mut found_array = false;
         let mut frame_positions = Vec::new();
-        let mut i = 0;
-        let mut total_duration = 0;
 
-        while let Some(pos) = c[i..].find("{") {
-            frame_positions.push(i + pos);
-            i += pos + 4;
+        for (i, &character) in chars.iter().enumerate() {
+            match character {
+                '{' => {
+                    if depth == 0 && found_array {break;}
+                    if depth == 0 {found_array = true;}
+                    if depth == 1 {frame_positions.push(i);}
+                    depth += 1;
+                },
+                '}' if depth > 0 => depth -= 1,
+                _ => {}
+            }
         }
-        frame_positions.remove(0);
 
-        let mut frames = Vec::new();
+        if !found_array {
+            return Err(ParseError::MissingArray);
+        }
+
+        let mut entries = Vec::new();
 
         for pos in frame_positions.into_iter() {
             let mut cel_name = String::new();
             let mut duration_str = String::new();
 
-            i = pos + 1;
-            while c.chars().nth(i) != Some(',') {
-                if c.chars().nth(i) != Some(' ') {
-                    cel_name.push(c.chars().nth(i).unwrap());
+            let mut i = pos + 1;
+            while *chars.get(i).ok_or(ParseError::MissingTerminator)? != ',' {
+                if !chars[i].is_whitespace() {
+                    cel_name.push(chars[i]);
                 }
                 i += 1;
             }
 
             i += 1;
 
-            while c.chars().nth(i) != Some('}') {
-                if c.chars().nth(i) != Some(' ') {
-                    duration_str.push(c.chars().nth(i).unwrap());
+            while *chars.get(i).ok_or(ParseError::MissingTerminator)? != '}' {
+                if !chars[i].is_whitespace() {
+                    duration_str.push(chars[i]);
                 }
                 i += 1;
             }
 
             let duration = match duration_str.parse() {
                 Ok(value) => value,
-                Err(_) => return None,
+                Err(_) => return Err(ParseError::BadDuration(duration_str)),
             };
 
-            frames.push(AnimationFrame {
-                cell: cel_name,
-                duration,
-                id: frames.len()
-            });
-            
-            total_duration += duration as usize;
+            entries.push((cel_name, duration));
+        }
+
+        Ok(entries)
+    }
+
+    pub fn from_c(c: &str, name: &str) -> Result<Animation, ParseError> {
+        Ok(Animation::from_frame_list(name, &Animation::parse_c_entries(c)?))
+    }
+
+    // For tables that reference cels by their position in a cel array instead of by name
+    pub fn from_c_indexed(c: &str, name: &str, cel_names: &[String]) -> Result<Animation, ParseError> {
+        let mut frames = Vec::new();
+
+        for (index, duration) in Animation::parse_c_entries(c)? {
+            let cell = match index.parse::<usize>().ok().and_then(|i| cel_names.get(i)) {
+                Some(cell) => cell.clone(),
+                None => return Err(ParseError::BadCelIndex(index))
+            };
+
+            frames.push(AnimationFrame { cell, duration, id: 0, hold: false });
         }
 
-        Some(Animation { frames, name: name.to_string(), current_frame: 0, duration: total_duration })
+        Ok(Animation::from_frames(name, frames))
     }
 
-    pub fn from_bin(bin: &[u8]) -> Option<Animation> {
-        let mut name = String::from("");
+    pub fn from_c_opt(c: &str, name: &str) -> Option<Animation> {
+        Animation::from_c(c, name).ok()
+    }
+
+    // Name, then the 3 bytes after it: the 0x00 separator and the big endian byte length of the frame
+    // records that follow (see to_bin). Doesn't look at the records themselves
+    pub fn read_bin_header(bin: &[u8]) -> Result<AnimationBinHeader, ParseError> {
         let mut i = 0;
-        let mut frame_id = 0;
-        let mut duration = 0;
 
-        while bin[i] != 0x00 {
-            name.push(bin[i] as char);
+        while *bin.get(i).ok_or(ParseError::MissingTerminator)? != 0x00 {
             i += 1;
         }
+
+        let name = decode_name(&bin[..i]);
         
-        // Skip over animation length
-        i += 3;
+        let upper_byte = *bin.get(i + 1).ok_or(ParseError::Truncated)? as usize;
+        let lower_byte = *bin.get(i + 2).ok_or(ParseError::Truncated)? as usize;
+
+        Ok(AnimationBinHeader { name, frames_length: (upper_byte << 8) | lower_byte, header_length: i + 3 })
+    }
+
+    pub fn from_bin(bin: &[u8]) -> Result<Animation, ParseError> {
+        let AnimationBinHeader { name, frames_length: declared, header_length } = Animation::read_bin_header(bin)?;
+        let mut i = header_length;
+
+        let actual = bin.len() - i;
+        if declared != actual {
+            return Err(ParseError::LengthMismatch { declared, actual });
+        }
 
         let mut frames = Vec::new();
-        let mut cell = String::from("");
+        let mut cell = Vec::new();
         while i < bin.len() {
             
             if bin[i] != 0x00 {
-                cell.push(bin[i] as char);
+                cell.push(bin[i]);
             } else {
                 i += 1; // Go to duration byte
                 frames.push(AnimationFrame {
-                    cell,
-                    duration: bin[i],
-                    id: frame_id
+                    cell: decode_name(&cell),
+                    duration: *bin.get(i).ok_or(ParseError::Truncated)? as u16,
+                    id: 0,
+                    hold: false
                 });
-                duration += bin[i] as usize;
-                frame_id += 1;
-                cell = String::from("");
+                cell.clear();
             }
             
             i += 1;
             
         }
 
-        Some(Animation { frames, name, current_frame: 0, duration })
+        // Leftover name bytes means the last record never got its separator and duration
+        if !cell.is_empty() {
+            return Err(ParseError::Truncated);
+        }
+
+        Ok(Animation::from_frames(&name, frames))
+    }
+
+    // Layout described at the top of export.rs: name, 0x00, then the length in bytes of the frame records
+    // as a big-endian u16 (the 3 bytes from_bin skips), then a name, 0x00, duration record per frame.
+    // Durations only get 1 byte there, so frames longer than 255 ticks are written as several records of the same cel.
+    // Fails when the records come to more than u16::MAX bytes
+    pub fn to_bin(&self) -> Result<Vec<u8>, WriteError> {
+        let mut frame_bytes = Vec::new();
+
+        for frame in &self.frames {
+            let mut remaining = frame.duration;
+
+            loop {
+                let duration = remaining.min(u8::MAX as u16);
+
+                frame_bytes.extend(frame.cell.as_bytes());
+                frame_bytes.push(0x00);
+                frame_bytes.push(duration as u8);
+
+                remaining -= duration;
+                if remaining == 0 {break;}
+            }
+        }
+
+        let length = u16::try_from(frame_bytes.len()).map_err(|_| WriteError::FramesTooLong { length: frame_bytes.len() })?;
+
+        let mut bytes = Vec::new();
+
+        bytes.extend(self.name.as_bytes());
+        bytes.push(0x00);
+        bytes.push((length >> 8) as u8);
+        bytes.push((length & 0xFF) as u8);
+        bytes.extend(frame_bytes);
+
+        Ok(bytes)
+    }
+
+    // One animation per frame tag (or a single one named cel_name_prefix when there are none), with frames
+    // showing the cel <cel_name_prefix><aseprite frame index> and the millisecond durations converted to 60fps ticks
+    pub fn from_aseprite_json(json: &str, cel_name_prefix: &str) -> Result<Vec<Animation>, serde_json::Error> {
+        let export: AsepriteExport = serde_json::from_str(json)?;
+        let frames = export.frames.0;
+
+        let to_frame_list = |indices: Vec<usize>| -> Vec<(String, u16)> {
+            indices.into_iter()
+                .filter(|&i| i < frames.len())
+                .map(|i| {
+                    let ticks = (frames[i].duration * 60.0 / 1000.0).round().clamp(1.0, u16::MAX as f32) as u16;
+                    (format!("{}{}", cel_name_prefix, i), ticks)
+                })
+                .collect()
+        };
+
+        if export.meta.frame_tags.is_empty() {
+            return Ok(vec![Animation::from_frame_list(cel_name_prefix, &to_frame_list((0..frames.len()).collect()))]);
+        }
+
+        let animations = export.meta.frame_tags.iter().map(|tag| {
+            let indices: Vec<usize> = match tag.direction.as_str() {
+                "reverse" => (tag.from..=tag.to).rev().collect(),
+                // Forwards then back without repeating the end frames, so it loops cleanly
+                "pingpong" => (tag.from..=tag.to).chain((tag.from + 1..tag.to).rev()).collect(),
+                _ => (tag.from..=tag.to).collect()
+            };
+
+            Animation::from_frame_list(&tag.name, &to_frame_list(indices))
+        }).collect();
+
+        Ok(animations)
+    }
+
+    pub fn from_bin_opt(bin: &[u8]) -> Option<Animation> {
+        Animation::from_bin(bin).ok()
+    }
+
+    // Same check as AnimationCel::bin_roundtrip_mismatch. from_bin already rejects trailing bytes through the
+    // declared length, so the whole input is compared. Invalid UTF-8 in names is the only expected difference.
+    // Records are written back one for one, so to_bin can't outgrow a length that was read from a header
+    pub fn bin_roundtrip_mismatch(bin: &[u8]) -> Result<Option<usize>, ParseError> {
+        Ok(first_mismatch(bin, &Animation::from_bin(bin)?.to_bin().unwrap_or_default()))
+    }
+
+    // Reads exactly one animation, so concatenated animations can be read one after another from the same reader
+    pub fn from_bin_reader<R: Read>(reader: &mut R) -> Result<Animation, ParseError> {
+        let mut bytes = Vec::new();
+
+        read_name_bytes(reader, &mut bytes)?;
+        read_bytes(reader, &mut bytes, 2)?;
+
+        let length = ((bytes[bytes.len() - 2] as usize) << 8) | bytes[bytes.len() - 1] as usize;
+        read_bytes(reader, &mut bytes, length)?;
+
+        Animation::from_bin(&bytes)
+    }
+
+    /*pub fn get_total_frame_duration(&self, index: usize) -> usize {
+        let mut result = 0;
+        
+        for i in 0..index {
+            result += self.frames[i].duration as usize;
+        }
+
+        result
+    }*/
+
+    pub fn get_total_frames(&self) -> usize {
+        let mut result = 0;
+
+        for frame in &self.frames {
+            result += frame.duration as usize;
+        }
+        
+        result
+    }
+
+    // Number of cels shown, get_total_frames is the length in ticks
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // (frame id, duration) pairs, longest first, frames with equal durations keep their order
+    pub fn frames_by_duration(&self) -> Vec<(usize, u16)> {
+        let mut frames: Vec<(usize, u16)> = self.frames.iter().map(|frame| (frame.id, frame.duration)).collect();
+        frames.sort_by_key(|&(_, duration)| std::cmp::Reverse(duration));
+
+        frames
+    }
+    
+    // Drops the cached frame ends, for code that edits frames without going through the methods here
+    pub fn frames_changed(&mut self) {
+        self.frame_ends.take();
+    }
+
+    // Cached running total of the frame durations. A cache left over from before a direct edit that skipped
+    // frames_changed is caught by its length where possible and rebuilt without being stored
+    fn frame_ends(&self) -> Cow<'_, [usize]> {
+        let build = || self.frames.iter().scan(0, |end, frame| {
+            *end += frame.duration as usize;
+            Some(*end)
+        }).collect::<Vec<usize>>();
+
+        let ends = self.frame_ends.get_or_init(build);
+
+        if ends.len() == self.frames.len() {
+            Cow::Borrowed(ends)
+        } else {
+            Cow::Owned(build())
+        }
+    }
+
+    // Frame the playhead is on after frames ticks, 0 past the end. A tick that lands exactly on a frame's
+    // end still counts as that frame. Binary search over the cached frame ends, so it's O(log frames)
+    pub fn get_anim_frame_from_frames(&self, frames: usize) -> usize {
+        if frames == 0 {return 0;}
+
+        let ends = self.frame_ends();
+        let index = ends.partition_point(|&end| end < frames);
+
+        if index < ends.len() {index} else {0}
+    }
+
+    // Frame that covers tick in start..end, the convention advance_with_callback, current_tick and the editor
+    // playhead use, so a tick equal to a frame's end already belongs to the next frame. get_anim_frame_from_frames
+    // counts the end tick as part of the frame instead. Ticks past the end stay on the last frame rather than
+    // going back to 0
+    pub fn frame_index_at_tick_clamped(&self, tick: usize) -> usize {
+        let ends = self.frame_ends();
+
+        ends.partition_point(|&end| end <= tick).min(ends.len().saturating_sub(1))
+    }
+
+    // Moves the playhead forward, looping at the end, and calls on_frame with the index
+    // of every frame that gets entered along the way
+    pub fn advance_with_callback(&mut self, ticks: usize, mut on_frame: impl FnMut(usize)) {
+        let total_frames = self.get_total_frames();
+        if total_frames == 0 {return;}
+
+        // Whole ticks are stepped one at a time below so wrapping and frame changes stay exact at any speed
+        let scaled_ticks = ticks as f32 * self.speed.max(0.0) + self.tick_remainder;
+        let ticks = scaled_ticks.floor() as usize;
+        self.tick_remainder = scaled_ticks.fract();
+
+        let mut frame_starts = Vec::new();
+        let mut start = 0;
+
+        for frame in &self.frames {
+            frame_starts.push(start);
+            start += frame.duration as usize;
+        }
+
+        for _ in 0..ticks {
+            // On the last tick of a held frame nothing moves until the hold is released
+            let frame = frame_starts.partition_point(|&start| start <= self.current_tick) - 1;
+            let frame_end = frame_starts[frame] + self.frames[frame].duration as usize;
+            
+            if self.frames[frame].hold && self.current_tick + 1 >= frame_end {
+                if !self.hold_released {
+                    self.tick_remainder = 0.0;
+                    return;
+                }
+
+                self.hold_released = false;
+            }
+
+            self.current_tick += 1;
+
+            let wrapped = self.current_tick >= total_frames;
+            if wrapped {
+                self.current_tick = 0;
+            }
+
+            let frame = frame_starts.partition_point(|&start| start <= self.current_tick) - 1;
+
+            if frame != self.current_frame || wrapped {
+                self.current_frame = frame;
+                on_frame(frame);
+            }
+        }
+    }
+
+    // Whether advancing is blocked on a held frame, as opposed to time still passing within a frame
+    pub fn is_held(&self) -> bool {
+        if self.frames.is_empty() {return false;}
+        
+        let index = self.frame_index_at_tick_clamped(self.current_tick);
+        let frame_end: usize = self.frames[..=index].iter().map(|frame| frame.duration as usize).sum();
+
+        self.frames[index].hold && !self.hold_released && self.current_tick + 1 >= frame_end
+    }
+
+    // Lets playback continue past the held frame it's stopped on, or the next one it reaches
+    pub fn release_hold(&mut self) {
+        self.hold_released = true;
+    }
+
+    // fps with the 60 default filled in, a rate that isn't positive is ignored so it can't divide by zero
+    pub fn tick_rate(&self) -> f32 {
+        self.fps.filter(|&fps| fps > 0.0).unwrap_or(60.0)
+    }
+
+    pub fn duration_seconds(&self) -> f32 {
+        self.get_total_frames() as f32 / self.tick_rate()
+    }
+
+    pub fn tick_to_seconds(&self, tick: usize) -> f32 {
+        tick as f32 / self.tick_rate()
+    }
+
+    // Last tick that has started by the given time
+    pub fn seconds_to_tick(&self, seconds: f32) -> usize {
+        (seconds.max(0.0) * self.tick_rate()) as usize
+    }
+
+    // (cel, start tick, end tick) for every frame in timeline order, the end being exclusive
+    pub fn frame_intervals(&self) -> Vec<(String, usize, usize)> {
+        let mut start = 0;
+
+        self.frames.iter().map(|frame| {
+            let end = start + frame.duration as usize;
+            let interval = (frame.cell.clone(), start, end);
+
+            start = end;
+            interval
+        }).collect()
     }
 
-    /*pub fn get_total_frame_duration(&self, index: usize) -> usize {
-        let mut result = 0;
-        
-        for i in 0..index {
-            result += self.frames[i].duration as usize;
-        }
+    // Start of the frame after the one the editor playhead shows at tick (see frame_index_at_tick_clamped),
+    // so sitting on a boundary moves on to the next one. Zero length frames are never shown and get skipped
+    pub fn next_frame_boundary(&self, tick: usize) -> Option<usize> {
+        let current = self.frame_index_at_tick_clamped(tick);
 
-        result
-    }*/
+        self.frame_intervals()
+            .into_iter()
+            .skip(current + 1)
+            .find(|(_, start, end)| end > start)
+            .map(|(_, start, _)| start)
+    }
 
-    pub fn get_total_frames(&self) -> usize {
-        let mut result = 0;
+    // Start of the frame before the one shown at tick
+    pub fn prev_frame_boundary(&self, tick: usize) -> Option<usize> {
+        let current = self.frame_index_at_tick_clamped(tick);
+
+        self.frame_intervals()
+            .into_iter()
+            .enumerate()
+            .take(current)
+            .rev()
+            .find(|(i, (_, start, end))| *i == 0 || end > start)
+            .map(|(_, (_, start, _))| start)
+    }
 
-        for frame in &self.frames {
-            result += frame.duration as usize;
-        }
-        
-        result
+    // Timeline coordinate conversions, tick is clamped to 0..=total so the two directions agree at the ends
+    pub fn tick_to_x(&self, tick: usize, px_per_tick: f32, origin_x: f32) -> f32 {
+        origin_x + tick.min(self.get_total_frames()) as f32 * px_per_tick
     }
-    
-    pub fn get_anim_frame_from_frames(&self, frames: usize) -> usize {
-        if frames > self.get_total_frames() {
-            return 0;
-        }
 
-        if self.frames.len() == 0 {
-            return 0;
-        }
+    // Rounds to the nearest tick boundary, so x_to_tick(tick_to_x(tick)) == tick
+    pub fn x_to_tick(&self, x: f32, px_per_tick: f32, origin_x: f32) -> usize {
+        if px_per_tick <= 0.0 {return 0;}
 
-        let mut result = 0;
-        let mut i = 0;
-        let mut current_frame = &self.frames[0];
-        
-        for _ in 0..frames {
-            if i == current_frame.duration {
-                i = 0;
-                result += 1;
-                current_frame = &self.frames[result];
-            }
-            i += 1;
-        }
+        let tick = ((x - origin_x) / px_per_tick).round().max(0.0) as usize;
+        tick.min(self.get_total_frames())
+    }
 
-        result
+    // Selection lives in the timeline panel, so the ids of the selected frames are passed in
+    pub fn timeline_model(&self, selected_ids: &[usize]) -> Vec<TimelineSegment> {
+        let mut start = 0;
+
+        self.frames.iter().map(|frame| {
+            let segment = TimelineSegment {
+                start,
+                width: frame.duration as usize,
+                cel: frame.cell.clone(),
+                id: frame.id,
+                selected: selected_ids.contains(&frame.id)
+            };
+
+            start += frame.duration as usize;
+            segment
+        }).collect()
     }
 
     pub fn convert_duration_frames_to_positioned(frames: &Vec<AnimationFrame>) -> Vec<PositionedAnimationFrame> {
@@ -545,7 +2301,8 @@ impl Animation {
             positioned_frames.push(PositionedAnimationFrame {
                 cell: frame.cell.clone(),
                 position: total_duration,
-                id: frame.id
+                id: frame.id,
+                hold: frame.hold
             });
 
             total_duration += frame.duration as isize;
@@ -555,11 +2312,11 @@ impl Animation {
     }
 
     pub fn convert_positioned_frames_to_duration(mut frames: Vec<PositionedAnimationFrame>, duration: usize) -> Vec<AnimationFrame> {
-        frames.sort_by(|a, b| a.position.cmp(&b.position));
+        frames.sort_by_key(|frame| frame.position);
         
         let mut duration_frames = Vec::new();
         
-        if frames.len() == 0 {
+        if frames.is_empty() {
             return duration_frames;
         }
 
@@ -573,15 +2330,31 @@ impl Animation {
 
             duration_frames.push(AnimationFrame { 
                 cell: frame.cell.clone(), 
-                duration: (next_frame_pos - frame.position) as u8,
-                id: frame.id
+                duration: clamp_duration(next_frame_pos - frame.position, "convert_positioned_frames_to_duration"),
+                id: frame.id,
+                hold: frame.hold
             });
         }
 
         duration_frames
     }
     
+    // Gives the frames ids 0..n in timeline order. The frame editing functions look frames up by id
+    // and treat id 0 as the first frame, so they rely on ids being unique, which this guarantees
+    pub fn reindex_frames(&mut self) {
+        for (i, frame) in self.frames.iter_mut().enumerate() {
+            frame.id = i;
+        }
+    }
+
+    pub fn has_duplicate_ids(&self) -> bool {
+        let mut seen = HashSet::new();
+        !self.frames.iter().all(|frame| seen.insert(frame.id))
+    }
+
+    // Requires unique frame ids, see reindex_frames
     pub fn move_anim_frame(&mut self, frame_id: usize, offset: isize) -> Option<()> {
+        self.frames_changed();
         if frame_id == 0 {return None}
         if offset == 0 {return None}
         
@@ -595,53 +2368,777 @@ impl Animation {
     }
     
     pub fn insert_anim_frame(&mut self, cell: String, position: isize) {
+        self.frames_changed();
         let mut positioned_frames = Animation::convert_duration_frames_to_positioned(&self.frames);
         
-        positioned_frames.push(PositionedAnimationFrame { cell, position, id: positioned_frames.len() + 1 });
+        // The length can be an id that's still in use once frames have been removed
+        let id = positioned_frames.iter().map(|frame| frame.id + 1).max().unwrap_or(0);
+        positioned_frames.push(PositionedAnimationFrame { cell, position, id, hold: false });
         
         self.frames = Animation::convert_positioned_frames_to_duration(positioned_frames, self.duration);
     }
     
+    // Requires unique frame ids, see reindex_frames
     pub fn remove_anim_frame(&mut self, frame_id: usize) {
+        self.frames_changed();
         if frame_id == 0 {return;}
         
         if let Some(index) = self.frames.iter().position(|f| f.id == frame_id) {
             let duration = self.frames[index].duration;
-            self.frames.remove(index);
-            
-            self.frames[index - 1].duration += duration;
+
+            match self.frames[index - 1].duration.checked_add(duration) {
+                Some(merged) => {
+                    self.frames.remove(index);
+                    self.frames[index - 1].duration = merged;
+                }
+                // Too long for one frame, so the previous frame fills u16::MAX ticks and the removed frame becomes
+                // the rest of it. The hold moves to the end like in split_frame_at
+                None => {
+                    let previous = &mut self.frames[index - 1];
+                    let remainder = (previous.duration as usize + duration as usize - u16::MAX as usize) as u16;
+                    let cell = previous.cell.clone();
+                    let hold = previous.hold;
+
+                    previous.duration = u16::MAX;
+                    previous.hold = false;
+
+                    let frame = &mut self.frames[index];
+                    frame.cell = cell;
+                    frame.duration = remainder;
+                    frame.hold = hold;
+                }
+            }
+        }
+    }
+
+    // Splits the frame playing at tick in two so that a frame starts exactly at tick,
+    // returns the index of the frame starting there
+    pub fn split_frame_at(&mut self, tick: usize) -> Option<usize> {
+        self.frames_changed();
+        let mut start = 0;
+
+        for i in 0..self.frames.len() {
+            let duration = self.frames[i].duration as usize;
+
+            if tick == start {
+                return Some(i);
+            }
+
+            if tick < start + duration {
+                let id = self.frames.iter().map(|f| f.id).max().unwrap_or(0) + 1;
+
+                self.frames[i].duration = (tick - start) as u16;
+
+                // The hold happens at the end of the frame, so it moves to the second half
+                let hold = self.frames[i].hold;
+                self.frames[i].hold = false;
+
+                self.frames.insert(i + 1, AnimationFrame {
+                    cell: self.frames[i].cell.clone(),
+                    duration: (start + duration - tick) as u16,
+                    id,
+                    hold
+                });
+
+                return Some(i + 1);
+            }
+
+            start += duration;
+        }
+
+        None
+    }
+
+    // Add keyframe: copies the cel showing at current_tick to new_cel_name and inserts a frame of it at the playhead,
+    // splitting the frame under the playhead. The animation gets longer by duration. Returns the new frame's index,
+    // None if there's nothing showing or new_cel_name is already taken
+    pub fn insert_keyframe_at_playhead(&mut self, cels: &mut HashMap<String, AnimationCel>, new_cel_name: String, duration: u16) -> Option<usize> {
+        if cels.contains_key(&new_cel_name) {return None;}
+
+        let showing = self.frames.get(self.frame_index_at_tick_clamped(self.current_tick))?;
+        let mut cel = cels.get(&showing.cell)?.clone();
+        cel.name = new_cel_name.clone();
+        cels.insert(new_cel_name.clone(), cel);
+
+        let index = self.split_frame_at(self.current_tick).unwrap_or(self.frames.len());
+
+        // Reindexed so id 0 stays the first frame when inserting at tick 0
+        self.frames.insert(index, AnimationFrame { cell: new_cel_name, duration, id: 0, hold: false });
+        self.frames_changed();
+        self.reindex_frames();
+        self.duration = self.get_total_frames();
+        self.current_frame = index;
+
+        Some(index)
+    }
+
+    pub fn split_at(&self, tick: usize, name_a: &str, name_b: &str) -> (Animation, Animation) {
+        let mut animation = self.clone();
+        
+        let index = match animation.split_frame_at(tick) {
+            Some(index) => index,
+            None => animation.frames.len()
+        };
+
+        let second_half = animation.frames.split_off(index);
+
+        (Animation::from_frames(name_a, animation.frames), Animation::from_frames(name_b, second_half))
+    }
+
+    // Builds an animation spanning exactly the given frames, ids are reassigned so the first frame is frame 0
+    pub fn from_frames(name: &str, frames: Vec<AnimationFrame>) -> Animation {
+        let mut animation = Animation {
+            frames,
+            name: name.to_string(),
+            current_frame: 0,
+            duration: 0,
+            current_tick: 0,
+            fps: None,
+            speed: 1.0,
+            tick_remainder: 0.0,
+            hold_released: false,
+            frame_ends: OnceLock::new()
+        };
+        animation.reindex_frames();
+        animation.duration = animation.get_total_frames();
+
+        animation
+    }
+
+    pub fn from_frame_list(name: &str, frames: &[(String, u16)]) -> Animation {
+        let frames = frames.iter()
+            .map(|(cell, duration)| AnimationFrame { cell: cell.clone(), duration: *duration, id: 0, hold: false })
+            .collect();
+
+        Animation::from_frames(name, frames)
+    }
+
+    // Multiplies the durations of frames start_index..=end_index by factor, every frame keeps at least 1 tick
+    pub fn scale_range_timing(&mut self, start_index: usize, end_index: usize, factor: f32) {
+        self.frames_changed();
+        let end_index = end_index.min(self.frames.len().saturating_sub(1));
+        if self.frames.is_empty() || start_index > end_index {return;}
+
+        for frame in &mut self.frames[start_index..=end_index] {
+            frame.duration = (frame.duration as f32 * factor).round().clamp(1.0, u16::MAX as f32) as u16;
+        }
+
+        self.duration = self.get_total_frames();
+    }
+
+    // Merges the adjacent pair with the shortest combined duration until there are at most max_frames frames.
+    // The merged frame shows whichever cel was on screen longer and the total duration doesn't change.
+    // Stops early if every remaining merge would overflow a frame's duration
+    pub fn quantize_to_frames(&mut self, max_frames: usize) {
+        self.frames_changed();
+        while self.frames.len() > max_frames.max(1) {
+            let shortest = (0..self.frames.len() - 1)
+                .map(|i| (i, self.frames[i].duration as usize + self.frames[i + 1].duration as usize))
+                .filter(|&(_, duration)| duration <= u16::MAX as usize)
+                .min_by_key(|&(_, duration)| duration);
+
+            let (i, duration) = match shortest {
+                Some(shortest) => shortest,
+                None => break
+            };
+
+            let next = self.frames.remove(i + 1);
+            if next.duration > self.frames[i].duration {
+                self.frames[i].cell = next.cell;
+            }
+            self.frames[i].duration = duration as u16;
+        }
+
+        self.current_frame = self.current_frame.min(self.frames.len().saturating_sub(1));
+    }
+
+    // Drops leading and trailing frames that show nothing (missing or OAM-less cels, or zero duration)
+    // so the duration tightly bounds the frames that actually have content
+    pub fn trim(&mut self, cels: &HashMap<String, AnimationCel>) {
+        self.frames_changed();
+        let has_content = |frame: &AnimationFrame| {
+            frame.duration > 0 && cels.get(&frame.cell).is_some_and(|cel| !cel.oams.is_empty())
+        };
+
+        let start = match self.frames.iter().position(has_content) {
+            Some(start) => start,
+            None => {
+                self.frames.clear();
+                self.duration = 0;
+                return;
+            }
+        };
+
+        let end = self.frames.iter().rposition(has_content).unwrap_or(start);
+
+        self.frames.truncate(end + 1);
+        self.frames.drain(..start);
+
+        // The new first frame has to become frame 0
+        for (i, frame) in self.frames.iter_mut().enumerate() {
+            frame.id = i;
+        }
+
+        self.duration = self.get_total_frames();
+    }
+
+    pub fn append(&mut self, other: &Animation) {
+        self.frames_changed();
+        // Only the very first frame may have id 0, so appended frames continue after the highest id
+        let next_id = match self.frames.iter().map(|f| f.id).max() {
+            Some(id) => id + 1,
+            None => 0
+        };
+
+        for (i, frame) in other.frames.iter().enumerate() {
+            self.frames.push(AnimationFrame {
+                cell: frame.cell.clone(),
+                duration: frame.duration,
+                id: next_id + i,
+                hold: frame.hold
+            });
         }
+
+        self.duration = self.get_total_frames();
     }
 
     pub fn get_minimum_duration(&self) -> usize {
         let positioned_frames = Animation::convert_duration_frames_to_positioned(&self.frames);
             
-        if positioned_frames.len() == 0 {
+        if positioned_frames.is_empty() {
             return 0;
         }
 
-        if let Some(last_frame) = positioned_frames.get(positioned_frames.len() - 1) {
+        if let Some(last_frame) = positioned_frames.last() {
             last_frame.position as usize
         } else {
             0
         }
     }
 
+    // Sets the duration and resizes the last frame to fit, durations the last frame can't
+    // stretch to are rejected with the range of valid durations
+    pub fn set_total_duration(&mut self, duration: usize) -> Result<(), RangeInclusive<usize>> {
+        let minimum_duration = self.get_minimum_duration();
+        let valid_range = minimum_duration..=minimum_duration + u16::MAX as usize;
+
+        if !valid_range.contains(&duration) {
+            return Err(valid_range);
+        }
+
+        self.duration = duration;
+        self.update_duration();
+
+        Ok(())
+    }
+
     pub fn update_duration(&mut self) {
         let minimum_duration = self.get_minimum_duration();
+        let duration = clamp_duration(self.duration as isize - minimum_duration as isize, "update_duration");
+
+        // The timeline calls this every repaint, so the cache is only dropped when the duration really changes
         if let Some(frame) = self.frames.last_mut() {
-            frame.duration = (self.duration - minimum_duration) as u8;
+            if frame.duration != duration {
+                frame.duration = duration;
+                self.frame_ends.take();
+            }
+        }
+    }
+
+    // Amount of tiles shown in a frame that weren't shown in the frame before it,
+    // the first frame is compared against the last one since animations loop
+    pub fn tile_delta(&self, cels: &HashMap<String, AnimationCel>, frame_index: usize) -> usize {
+        let frame = match self.frames.get(frame_index) {
+            Some(frame) => frame,
+            None => return 0
+        };
+
+        let previous_index = if frame_index == 0 { self.frames.len() - 1 } else { frame_index - 1 };
+        
+        let tiles = match cels.get(&frame.cell) {
+            Some(cel) => cel.get_used_tiles(),
+            None => return 0
+        };
+
+        let previous_tiles = match cels.get(&self.frames[previous_index].cell) {
+            Some(cel) => cel.get_used_tiles(),
+            None => HashSet::new()
+        };
+
+        tiles.difference(&previous_tiles).count()
+    }
+
+    // OAMs each frame's cel takes up, 0 for missing cels. Disabled and window OAMs are counted too since
+    // they're still exported and take an OAM entry
+    pub fn oam_counts(&self, cels: &HashMap<String, AnimationCel>) -> Vec<usize> {
+        self.frames.iter()
+            .map(|frame| cels.get(&frame.cell).map_or(0, |cel| cel.oams.len()))
+            .collect()
+    }
+
+    // Compare against MAX_HARDWARE_OAMS
+    pub fn peak_oam_count(&self, cels: &HashMap<String, AnimationCel>) -> usize {
+        self.oam_counts(cels).into_iter().max().unwrap_or(0)
+    }
+
+    // Tiles used under each palette across every cel the animation shows. Missing cels are skipped
+    pub fn tiles_per_palette(&self, cels: &HashMap<String, AnimationCel>) -> HashMap<usize, HashSet<usize>> {
+        let mut tiles: HashMap<usize, HashSet<usize>> = HashMap::new();
+
+        for cel in self.get_used_cels().into_iter().filter_map(|name| cels.get(name)) {
+            for oam in &cel.oams {
+                tiles.entry(oam.palette).or_default().extend(oam.get_sprite_indexes_one_dimensional());
+            }
+        }
+
+        tiles
+    }
+
+    // Preview at a fractional tick. With smooth the frame's cel fades out towards the next frame's cel over the
+    // course of the frame, the last frame fading into the first since animations loop. Without it this is a hard cut
+    pub fn draw_scrubbed(&self, tick_f: f32, cels: &HashMap<String, AnimationCel>, textures: &[Vec<TextureHandle>], ui: &mut Ui, smooth: bool) {
+        let total_ticks = self.get_total_frames();
+        if total_ticks == 0 {return;}
+
+        let tick_f = tick_f.rem_euclid(total_ticks as f32);
+        let index = self.frame_index_at_tick_clamped(tick_f as usize);
+        let start: usize = self.frames[..index].iter().map(|frame| frame.duration as usize).sum();
+        let duration = self.frames[index].duration.max(1);
+
+        let current = cels.get(&self.frames[index].cell);
+        let next = cels.get(&self.frames[(index + 1) % self.frames.len()].cell);
+
+        if !smooth {
+            if let Some(current) = current {
+                current.draw(textures, ui, DrawOptions::default());
+            }
+            return;
+        }
+
+        let frac = ((tick_f - start as f32) / duration as f32).clamp(0.0, 1.0);
+
+        if let Some(current) = current {
+            current.draw_with_opacity(textures, ui, DrawOptions::default(), 1.0 - frac);
+        }
+
+        if let Some(next) = next {
+            next.draw_with_opacity(textures, ui, DrawOptions::default(), frac);
+        }
+    }
+
+    // Renders every frame side by side into a PNG filmstrip, each padded to the bounding box of the whole animation
+    pub fn export_strip(&self, cels: &HashMap<String, AnimationCel>, spritesheet: &Spritesheet, palette: &Palette, scale: u32, path: &str) -> Result<(), image::ImageError> {
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+
+        for frame in &self.frames {
+            let cel_bounds = match cels.get(&frame.cell).and_then(|cel| cel.get_pixel_bounds()) {
+                Some(cel_bounds) => cel_bounds,
+                None => continue
+            };
+
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(cel_bounds.0), y0.min(cel_bounds.1), x1.max(cel_bounds.2), y1.max(cel_bounds.3)),
+                None => cel_bounds
+            });
+        }
+
+        let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((0, 0, 1, 1));
+        let (width, height) = ((max_x - min_x) as u32, (max_y - min_y) as u32);
+
+        let mut image = RgbaImage::new(width * scale * self.frames.len().max(1) as u32, height * scale);
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            if let Some(cel) = cels.get(&frame.cell) {
+                cel.render_to_image(&mut image, spritesheet, palette, (i as i32 * width as i32 - min_x, -min_y), scale);
+            }
+        }
+
+        image.save(path)
+    }
+
+    // Texture packer style export: every distinct cel rendered once into a grid PNG, and a JSON listing the regions
+    // and which region each frame shows for how long. Regions share the bounding box of the whole animation so
+    // the origin is in the same place in all of them. Frames pointing at missing cels are left out
+    pub fn export_atlas(&self, cels: &HashMap<String, AnimationCel>, spritesheet: &Spritesheet, palette: &Palette, scale: u32, path_png: &str, path_json: &str) -> Result<(), image::ImageError> {
+        let used_cels: Vec<&AnimationCel> = self.get_used_cels().into_iter().filter_map(|name| cels.get(name)).collect();
+
+        let bounds = used_cels.iter()
+            .filter_map(|cel| cel.get_pixel_bounds())
+            .reduce(|(x0, y0, x1, y1), (u0, v0, u1, v1)| (x0.min(u0), y0.min(v0), x1.max(u1), y1.max(v1)));
+
+        let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((0, 0, 1, 1));
+        let (width, height) = ((max_x - min_x) as u32, (max_y - min_y) as u32);
+
+        // As close to square as the cel count allows
+        let columns = (used_cels.len() as f32).sqrt().ceil().max(1.0) as u32;
+        let rows = (used_cels.len() as u32).div_ceil(columns).max(1);
+
+        let mut image = RgbaImage::new(width * scale * columns, height * scale * rows);
+        let mut regions = Vec::new();
+
+        for (i, cel) in used_cels.iter().enumerate() {
+            let (x, y) = ((i as u32 % columns) * width, (i as u32 / columns) * height);
+
+            cel.render_to_image(&mut image, spritesheet, palette, (x as i32 - min_x, y as i32 - min_y), scale);
+            regions.push(AtlasRegion { cel: cel.name.clone(), x: x * scale, y: y * scale, width: width * scale, height: height * scale });
+        }
+
+        let frames = self.frames.iter()
+            .filter_map(|frame| Some(AtlasFrame {
+                region: regions.iter().position(|region| region.cel == frame.cell)?,
+                duration: frame.duration
+            }))
+            .collect();
+
+        let image_name = std::path::Path::new(path_png)
+            .file_name()
+            .map_or(String::from(path_png), |name| name.to_string_lossy().into_owned());
+
+        let atlas = AtlasExport {
+            name: self.name.clone(),
+            image: image_name,
+            origin_x: -min_x * scale as i32,
+            origin_y: -min_y * scale as i32,
+            regions,
+            frames
+        };
+
+        image.save(path_png)?;
+
+        let json = serde_json::to_vec_pretty(&atlas).map_err(std::io::Error::from)?;
+        std::fs::write(path_json, json)?;
+
+        Ok(())
+    }
+
+    // Frame ids and playback state are left out
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+
+        self.name.hash(&mut hasher);
+        self.fps.map(f32::to_bits).hash(&mut hasher);
+        self.frames.len().hash(&mut hasher);
+
+        for frame in &self.frames {
+            frame.cell.hash(&mut hasher);
+            frame.duration.hash(&mut hasher);
+            frame.hold.hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    // Points every frame showing old at new instead, returns how many frames changed
+    pub fn replace_cel_ref(&mut self, old: &str, new: &str) -> usize {
+        let mut replaced = 0;
+
+        for frame in &mut self.frames {
+            if frame.cell == old {
+                frame.cell = new.to_string();
+                replaced += 1;
+            }
         }
+
+        replaced
+    }
+
+    // Frames pointing at a missing cel come out empty, OAMs that aren't drawn as sprites (see is_drawn) are left out
+    pub fn to_metasprite(&self, cels: &HashMap<String, AnimationCel>) -> MetaspriteAnimation {
+        let frames = self.frames.iter().map(|frame| {
+            let entries = match cels.get(&frame.cell) {
+                Some(cel) => cel.to_render_list()
+                    .into_iter()
+                    .filter(|oam| oam.is_drawn())
+                    .map(|oam| {
+                        let (width, height) = oam.get_width_and_height();
+
+                        MetaspriteEntry {
+                            tile: oam.tile,
+                            palette: oam.palette,
+                            x: oam.x,
+                            y: oam.y,
+                            flip: oam.flip,
+                            width: width * 8,
+                            height: height * 8,
+                            obj_mode: oam.obj_mode,
+                            affine: oam.affine
+                        }
+                    })
+                    .collect(),
+                None => Vec::new()
+            };
+
+            MetaspriteFrame { duration: frame.duration, entries }
+        }).collect();
+
+        MetaspriteAnimation { name: self.name.clone(), frames }
     }
+
     pub fn get_used_cels(&self) -> Vec<&String> {
         let mut used_cels = Vec::new();
 
         for frame in &self.frames {
-            if !used_cels.iter().any(|&cel| cel == &frame.cell) {
+            if !used_cels.contains(&&frame.cell) {
                 used_cels.push(&frame.cell);
             }
         }
 
         used_cels
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parses bytes as an animation, or as a cel when they aren't one (the same order import_bin_dir tries them in),
+    // writes them back out and fails on the first byte that differs. See bin_roundtrip_mismatch for what's normalized
+    pub fn assert_bin_roundtrip(bytes: &[u8]) {
+        let mismatch = match Animation::bin_roundtrip_mismatch(bytes) {
+            Ok(mismatch) => mismatch,
+            Err(_) => AnimationCel::bin_roundtrip_mismatch(bytes).expect("bytes should parse as an animation or a cel")
+        };
+
+        if let Some(offset) = mismatch {
+            panic!("round trip differs at byte {}: {:02x?}", offset, &bytes[offset..bytes.len().min(offset + 8)]);
+        }
+    }
+
+    fn frame_list(animation: &Animation) -> Vec<(String, u16)> {
+        animation.frames.iter().map(|frame| (frame.cell.clone(), frame.duration)).collect()
+    }
+
+    #[test]
+    fn cel_bin_roundtrip() {
+        let cel = AnimationCel {
+            name: String::from("cel_walk_0"),
+            oams: vec![
+                OAM { shape: OAMShape::Horizontal, size: OAMSize::Size2, flip: OAMFlip::Both, x: 5, y: -100, palette: 3, tile: 300, ..Default::default() },
+                // Outside the i8 range on both sides
+                OAM { x: -200, y: 127, tile: 0x0FFF, ..Default::default() },
+                OAM { x: 255, y: -128, palette: 15, ..Default::default() }
+            ]
+        };
+
+        let bytes = cel.to_bin().unwrap();
+        let parsed = AnimationCel::from_bin(&bytes).unwrap();
+
+        assert!(parsed == cel);
+        assert_bin_roundtrip(&bytes);
+        assert_eq!(parsed.oams.iter().map(|oam| oam.x).collect::<Vec<i16>>(), vec![5, -200, 255]);
+    }
+
+    #[test]
+    fn cel_bin_rejects_what_from_bin_cant_read() {
+        let cel = |name: &str, oams: Vec<OAM>| AnimationCel { name: String::from(name), oams };
+
+        let longest = cel(&"c".repeat(MAX_NAME_LENGTH), vec![OAM::default(); u8::MAX as usize]);
+        let bytes = longest.to_bin().unwrap();
+        assert!(AnimationCel::from_bin(&bytes).unwrap() == longest);
+
+        assert_eq!(cel(&"c".repeat(MAX_NAME_LENGTH + 1), vec![]).to_bin(), Err(WriteError::NameTooLong { length: MAX_NAME_LENGTH + 1 }));
+        assert_eq!(cel("c", vec![OAM::default(); 256]).to_bin(), Err(WriteError::TooManyOAMs { count: 256 }));
+        assert_eq!(cel("c", vec![OAM { palette: 256, ..Default::default() }]).to_asm_string("c"), Err(WriteError::PaletteOutOfRange { palette: 256 }));
+    }
+
+    #[test]
+    fn animation_bin_roundtrip_splits_long_frames() {
+        let animation = Animation::from_frame_list("anim_walk", &[
+            (String::from("cel_walk_0"), 300),
+            (String::from("cel_walk_1"), 4)
+        ]);
+
+        let bytes = animation.to_bin().unwrap();
+        let parsed = Animation::from_bin(&bytes).unwrap();
+        assert_bin_roundtrip(&bytes);
+
+        assert_eq!(parsed.name, "anim_walk");
+        assert_eq!(frame_list(&parsed), vec![
+            (String::from("cel_walk_0"), 255),
+            (String::from("cel_walk_0"), 45),
+            (String::from("cel_walk_1"), 4)
+        ]);
+        assert_eq!(parsed.get_total_frames(), animation.get_total_frames());
+    }
+
+    #[test]
+    fn animation_bin_rejects_frames_past_the_length_limit() {
+        // Each frame of u16::MAX ticks is 257 records of 3 bytes, so 85 of them fill the u16 length exactly
+        let frames = |count: usize| vec![(String::from("c"), u16::MAX); count];
+
+        let bytes = Animation::from_frame_list("a", &frames(85)).to_bin().unwrap();
+        assert_eq!(Animation::read_bin_header(&bytes).unwrap().frames_length, u16::MAX as usize);
+        assert_eq!(Animation::from_bin(&bytes).unwrap().get_total_frames(), 85 * u16::MAX as usize);
+        assert_bin_roundtrip(&bytes);
+
+        assert_eq!(Animation::from_frame_list("a", &frames(86)).to_bin(), Err(WriteError::FramesTooLong { length: 86 * 771 }));
+    }
+
+    #[test]
+    fn remove_anim_frame_splits_merges_past_u16() {
+        let mut animation = Animation::from_frame_list("anim", &[
+            (String::from("cel_a"), 40000),
+            (String::from("cel_b"), 40000),
+            (String::from("cel_c"), 10)
+        ]);
+        animation.frames[0].hold = true;
+
+        animation.remove_anim_frame(animation.frames[1].id);
+
+        assert_eq!(frame_list(&animation), vec![
+            (String::from("cel_a"), u16::MAX),
+            (String::from("cel_a"), (80000 - u16::MAX as usize) as u16),
+            (String::from("cel_c"), 10)
+        ]);
+        assert_eq!(animation.frames.iter().map(|frame| frame.hold).collect::<Vec<bool>>(), vec![false, true, false]);
+
+        animation.remove_anim_frame(animation.frames[2].id);
+        assert_eq!(frame_list(&animation)[1], (String::from("cel_a"), (80010 - u16::MAX as usize) as u16));
+    }
+
+    #[test]
+    fn frame_lookups_follow_frame_edits() {
+        let mut animation = Animation::from_frame_list("anim", &[
+            (String::from("cel_a"), 3),
+            (String::from("cel_b"), 0),
+            (String::from("cel_c"), 2)
+        ]);
+
+        let lookups = |animation: &Animation| (0..7).map(|tick| animation.get_anim_frame_from_frames(tick)).collect::<Vec<usize>>();
+        let clamped = |animation: &Animation| (0..7).map(|tick| animation.frame_index_at_tick_clamped(tick)).collect::<Vec<usize>>();
+
+        // An end tick still belongs to its frame, the zero tick frame is never landed on and past the end wraps to 0
+        assert_eq!(lookups(&animation), vec![0, 0, 0, 0, 2, 2, 0]);
+        assert_eq!(clamped(&animation), vec![0, 0, 0, 2, 2, 2, 2]);
+
+        animation.scale_range_timing(0, 0, 2.0);
+        assert_eq!(lookups(&animation), vec![0, 0, 0, 0, 0, 0, 0]);
+
+        animation.frames[0].duration = 1;
+        animation.frames_changed();
+        assert_eq!(lookups(&animation), vec![0, 0, 2, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn metasprite_skips_window_oams_and_keeps_affine() {
+        let affine = OAMAffine { index: 3, scale_x: 0.5, scale_y: 2.0, double_size: true };
+        let cel = AnimationCel { name: String::from("cel_a"), oams: vec![
+            OAM { tile: 1, obj_mode: ObjMode::Window, ..Default::default() },
+            OAM { tile: 2, obj_mode: ObjMode::SemiTransparent, zindex: 1, ..Default::default() },
+            OAM { tile: 3, shape: OAMShape::Horizontal, size: OAMSize::Size1, affine: Some(affine), zindex: 2, ..Default::default() }
+        ]};
+        let cels = HashMap::from([(cel.name.clone(), cel)]);
+
+        let metasprite = Animation::from_frame_list("anim", &[(String::from("cel_a"), 4)]).to_metasprite(&cels);
+        let entries = &metasprite.frames[0].entries;
+
+        assert_eq!(entries.iter().map(|entry| entry.tile).collect::<Vec<usize>>(), vec![2, 3]);
+        assert_eq!((entries[0].obj_mode, entries[0].affine), (ObjMode::SemiTransparent, None));
+        assert_eq!((entries[1].width, entries[1].height, entries[1].affine), (32, 8, Some(affine)));
+    }
+
+    #[test]
+    fn hit_tests_share_the_scale() {
+        let mut cel = AnimationCel { name: String::from("cel_a"), oams: vec![
+            OAM { x: 0, ..Default::default() },
+            OAM { x: 16, ..Default::default() }
+        ]};
+
+        // An 8x8 OAM at x 16 covers 40..60 in draw's coordinates, so 80..120 at scale 2
+        let point = pos2(100.0, 10.0);
+        let scale = 2.0;
+
+        assert_eq!(cel.oam_at(point, scale), Some(1));
+        assert_eq!(cel.oams_in_rect(Rect::from_center_size(point, vec2(1.0, 1.0)), scale), vec![1]);
+        assert!(cel.screen_bounds(scale).contains(point));
+        assert_eq!(cel.oam_at(point, 1.0), None);
+
+        assert_eq!(cel.toggle_select_at(point, scale, false), Some(1));
+        assert!(cel.oams[1].selected && !cel.oams[0].selected);
+    }
+
+    #[test]
+    fn frame_boundaries_follow_the_clamped_playhead() {
+        let animation = Animation::from_frame_list("anim", &[
+            (String::from("cel_a"), 3),
+            (String::from("cel_b"), 0),
+            (String::from("cel_c"), 2)
+        ]);
+
+        // Frame starts are 0 and 3, the zero length frame is skipped and past the end stays on the last frame
+        assert_eq!(animation.next_frame_boundary(0), Some(3));
+        assert_eq!(animation.next_frame_boundary(3), None);
+        assert_eq!(animation.prev_frame_boundary(4), Some(0));
+        assert_eq!(animation.prev_frame_boundary(9), Some(0));
+        assert_eq!(animation.frame_index_at_tick_clamped(9), 2);
+    }
+
+    #[test]
+    fn old_format_cel_loads_x_as_i8() {
+        let bytes = [
+            b'o', b'l', b'd', 0x00, 2,
+            // shape, size, flip, x, y, palette, tile
+            0, 1, 1, 0xFB, 0x10, 2, 0x00, 0x22,
+            2, 0, 3, 0x7F, 0x80, 0, 0x01, 0x00
+        ];
+
+        let cel = AnimationCel::from_bin(&bytes).unwrap();
+
+        assert_eq!(cel.name, "old");
+        assert_eq!((cel.oams[0].x, cel.oams[0].y, cel.oams[0].flip), (-5, 16, OAMFlip::Horizontal));
+        assert_eq!((cel.oams[1].x, cel.oams[1].y, cel.oams[1].flip), (127, -128, OAMFlip::Both));
+        assert_eq!((cel.oams[1].shape, cel.oams[1].tile), (OAMShape::Vertical, 0x100));
+
+        // Values that fit in an i8 are still written the old way
+        assert_bin_roundtrip(&bytes);
+    }
+
+    #[test]
+    fn wide_x_records_load_nine_bit_x() {
+        // Bit 7 of the flip byte marks the wide form, bit 6 is bit 8 of x
+        let negative = OAM::from_bin(&[0, 0, 0x80 | 0x40 | 2, 0x38, 0, 0, 0, 0]);
+        let positive = OAM::from_bin(&[0, 0, 0x80, 0xC8, 0, 0, 0, 0]);
+
+        assert_eq!((negative.x, negative.flip), (-200, OAMFlip::Vertical));
+        assert_eq!((positive.x, positive.flip), (200, OAMFlip::None));
+
+        assert_eq!(negative.to_bin()[2..4], [0xC2, 0x38]);
+        assert_eq!(positive.to_bin()[2..4], [0x80, 0xC8]);
+    }
+
+    #[test]
+    fn json_omits_default_fields() {
+        let json = serde_json::to_value(OAM::default()).unwrap();
+
+        for key in ["enabled", "affine", "obj_mode", "layer"] {
+            assert!(json.get(key).is_none(), "{} should be left out", key);
+        }
+
+        let oam = OAM { enabled: false, layer: Some(String::from("arm")), ..Default::default() };
+        let parsed: OAM = serde_json::from_value(serde_json::to_value(&oam).unwrap()).unwrap();
+        assert!(parsed == oam);
+
+        let animation = Animation::from_frame_list("anim_walk", &[(String::from("cel_walk_0"), 4)]);
+        let json = serde_json::to_value(&animation).unwrap();
+        assert!(json.get("fps").is_none());
+        assert!(json["frames"][0].get("hold").is_none());
+    }
+
+    #[test]
+    fn batched_meshes_keep_draw_order() {
+        let ctx = egui::Context::default();
+        let textures = vec![(0..2).map(|i| {
+            ctx.load_texture(format!("tile_{}", i), egui::ColorImage::new([8, 8], Color32::WHITE), Default::default())
+        }).collect::<Vec<TextureHandle>>()];
+
+        let mut meshes = Vec::new();
+
+        for tile in [0, 0, 1, 0] {
+            OAM { shape: OAMShape::Square, size: OAMSize::Size0, tile, ..Default::default() }.add_tiles_to_meshes(&textures, &mut meshes, Vec2::ZERO, 1.0);
+        }
+
+        // The last tile 0 is on top of tile 1, so it can't join the first mesh
+        let ids: Vec<egui::TextureId> = meshes.iter().map(|mesh| mesh.texture_id).collect();
+        assert_eq!(ids, vec![textures[0][0].id(), textures[0][1].id(), textures[0][0].id()]);
+        assert_eq!(meshes[0].vertices.len(), 8);
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use crate::anim_parser::{Animation, AnimationCel};
+
+// AnimationCels keyed by their name, which is how animation frames reference them
+#[derive(Default)]
+pub struct CelLibrary {
+    cels: HashMap<String, AnimationCel>
+}
+
+impl CelLibrary {
+    pub fn new() -> CelLibrary {
+        CelLibrary { cels: HashMap::new() }
+    }
+
+    pub fn from_map(cels: HashMap<String, AnimationCel>) -> CelLibrary {
+        CelLibrary { cels }
+    }
+
+    pub fn into_map(self) -> HashMap<String, AnimationCel> {
+        self.cels
+    }
+
+    // Read only, for the functions that take the plain map. Changes go through the library so names stay in sync
+    pub fn as_map(&self) -> &HashMap<String, AnimationCel> {
+        &self.cels
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.cels.keys()
+    }
+
+    pub fn cels_mut(&mut self) -> impl Iterator<Item = &mut AnimationCel> {
+        self.cels.values_mut()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.cels.contains_key(name)
+    }
+
+    // Returns the cel that was previously stored under the same name
+    pub fn insert(&mut self, cel: AnimationCel) -> Option<AnimationCel> {
+        self.cels.insert(cel.name.clone(), cel)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AnimationCel> {
+        self.cels.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut AnimationCel> {
+        self.cels.get_mut(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<AnimationCel> {
+        self.cels.remove(name)
+    }
+
+    // Renames a cel and repoints every frame that referenced the old name,
+    // fails if the old name doesn't exist or the new name is already taken
+    pub fn rename(&mut self, old_name: &str, new_name: &str, animations: &mut [Animation]) -> bool {
+        if self.cels.contains_key(new_name) {
+            return false;
+        }
+
+        let mut cel = match self.cels.remove(old_name) {
+            Some(cel) => cel,
+            None => return false
+        };
+
+        cel.name = new_name.to_string();
+        self.cels.insert(cel.name.clone(), cel);
+
+        for animation in animations {
+            animation.replace_cel_ref(old_name, new_name);
+        }
+
+        true
+    }
+}
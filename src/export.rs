@@ -1,8 +1,6 @@
 use std::{collections::HashMap, fs, io::Write};
 
-use serde::{Deserialize, Serialize};
-
-use crate::{anim_parser::{Animation, AnimationCel, OAMFlip, OAMShape, OAMSize}, ProjectStructure};
+use crate::{anim_parser::{Animation, AnimationCel, WriteError}, project::ProjectStructure};
 
 /*
     
@@ -23,9 +21,9 @@ For each animation cell:
         [but is it really worth it?? these files tend to be a few kilobytes at most]
         shape
         size
-        flip
-        x
-        y
+        flip (bit 7 set means x didn't fit in a signed byte, bit 6 is then bit 8 of the 9 bit x)
+        x (signed byte, or the low 8 bits of x when bit 7 of flip is set)
+        y (signed byte)
         palette
         tile (2 bytes, upper byte then lower byte)
 
@@ -38,39 +36,22 @@ For each animation:
     For each animation frame:
         Frame name
         0x00 seperator byte
-        Frame duration (1 byte)
+        Frame duration (1 byte, frames held for longer than 255 ticks are split into several records)
 */
 
 
 
-pub fn create_project_bin(path: &str, animation_cells: &HashMap<String, AnimationCel>, animations: &Vec<Animation>) {
+// Writer for the old .yan format above, saving goes through create_project_json now
+#[allow(dead_code)]
+pub fn create_project_bin(path: &str, animation_cells: &HashMap<String, AnimationCel>, animations: &Vec<Animation>) -> Result<(), WriteError> {
     let mut bytes: Vec<u8> = Vec::new();
 
     bytes.extend(String::from("YAN").as_bytes().to_vec());
     // We will set these bytes after putting in all the animation cells
     bytes.extend([0x00, 0x00, 0x00, 0x00]);
 
-    for (name, cell) in animation_cells {
-        // Cell name (saved as these may be editable in the future)
-        let name_bytes = name.as_bytes().to_vec();
-        bytes.extend(name_bytes);
-
-        // Seperator byte
-        bytes.push(0x00);
-
-        // Cell length
-        bytes.push(cell.oams.len() as u8);
-
-        for oam in &cell.oams {
-            bytes.push(oam.shape as u8);
-            bytes.push(oam.size as u8);
-            bytes.push(oam.flip as u8);
-            bytes.push(oam.x as u8);
-            bytes.push(oam.y as u8);
-            bytes.push(oam.palette as u8);
-            bytes.push((oam.tile >> 8) as u8);
-            bytes.push((oam.tile & 0xFF) as u8);
-        }
+    for cell in animation_cells.values() {
+        bytes.extend(cell.to_bin()?);
     }
 
     let animation_start_index: u32 = bytes.len() as u32;
@@ -81,48 +62,29 @@ pub fn create_project_bin(path: &str, animation_cells: &HashMap<String, Animatio
     bytes[6] = (animation_start_index & 0xFF) as u8;
 
     for animation in animations {
-        // Animation name
-        let name_bytes = animation.name.as_bytes().to_vec();
-        bytes.extend(name_bytes);
-
-        // Seperator byte
-        bytes.push(0x00);
-        
-        // Allocate 2 bytes for animation length
-        bytes.extend([0x00, 0x00]);
-        let start_length = bytes.len();
-
-        for frame in &animation.frames {
-            bytes.extend(frame.cell.as_bytes().to_vec());
-            bytes.push(0x00);
-            bytes.push(frame.duration);
-        }
-
-        let end_length = bytes.len();
-        let length = (end_length - start_length) as u16;
-
-        bytes[start_length - 2] = (length >> 8) as u8;
-        bytes[start_length - 1] = (length & 0xFF) as u8;
+        bytes.extend(animation.to_bin()?);
     }
 
     let export = fs::File::create(path);
     
     if let Ok(mut file) = export {
         let _ = file.write_all(&bytes);
-    }    
+    }
+
+    Ok(())
 }
 
-pub fn create_project_json(path: &str, animation_cells: &HashMap<String, AnimationCel>, animations: &Vec<Animation>) {
+pub fn create_project_json(path: &str, animation_cells: &HashMap<String, AnimationCel>, animations: &[Animation]) {
     let project = ProjectStructure {
         animation_cels: animation_cells.clone(),
-        animations: animations.clone()
+        animations: animations.to_vec()
     };
 
     let export = fs::File::create(path);
     
     if let Ok(mut file) = export {
-        if let Ok(bytes) = serde_json::to_vec_pretty(&project) {
-            let _ = file.write_all(&bytes);
+        if let Ok(json) = project.to_pretty_json() {
+            let _ = file.write_all(json.as_bytes());
         }
     }   
 }
@@ -137,55 +99,14 @@ pub fn export_animation_cels(path: &str, animation_cells: &HashMap<String, Anima
         let mut i = 0;
         
         let mut sorted_oams = cell.oams.clone();
-        sorted_oams.sort_by(|a, b| a.zindex.cmp(&b.zindex));
+        sorted_oams.sort_by_key(|oam| oam.zindex);
         
         for oam in sorted_oams {
             export.push_str(&format!("    /* {:0fill$} */ ", i, fill = 3));
-            let shape: u16 = match oam.shape {
-                OAMShape::Square => 0x0000,
-                OAMShape::Horizontal => 0x4000,
-                OAMShape::Vertical => 0x8000
-            };
-
-            let mut y = oam.y as i16;
-            if y < 0 {
-                y += 0x100;
-            }
-
-            let word1: u16 = shape | y as u16;
+            let [word1, word2, word3] = oam.to_oam_words();
 
             export.push_str(&format!("0x{:0fill$x}, ", word1, fill = 4));
-
-            let mut flip_size_nibble: u16 = match oam.size {
-                OAMSize::Size0 => 0x0000,
-                OAMSize::Size1 => 0x4000,
-                OAMSize::Size2 => 0x8000,
-                OAMSize::Size3 => 0xC000,
-            };
-
-            match oam.flip {
-                OAMFlip::None => {},
-                OAMFlip::Horizontal => {flip_size_nibble += 0x1000},
-                OAMFlip::Vertical => {flip_size_nibble += 0x2000},
-                OAMFlip::Both => {flip_size_nibble += 0x3000}
-            };
-
-            let mut x = oam.x as i16;
-            if x < 0 {
-                x += 0x200;
-            }
-
-            let word2: u16 = flip_size_nibble | x as u16;
-
             export.push_str(&format!("0x{:0fill$x}, ", word2, fill = 4));
-
-            let palette = (oam.palette as u16) << 12;
-
-            let tile = (oam.tile & 0x0FFF) as u16;
-
-            let word3: u16 = palette | tile;
-
-
             export.push_str(&format!("0x{:0fill$x}", word3, fill = 4));
 
             i += 1;
@@ -216,10 +137,19 @@ pub fn export_animations(path: &str, animations: &Vec<Animation>) {
 
         let mut i = 0;
         for frame in &animation.frames {
-            export.push_str(&format!("    /* {:0fill$} */ ", i, fill = 3));
-            export.push_str(&format!("{{ {}, {} }},\n", frame.cell, frame.duration));
-        
-            i += 1;
+            // The duration field in the C struct is a byte, so long frames become several entries like in to_bin
+            let mut remaining = frame.duration;
+
+            loop {
+                let duration = remaining.min(u8::MAX as u16);
+
+                export.push_str(&format!("    /* {:0fill$} */ ", i, fill = 3));
+                export.push_str(&format!("{{ {}, {} }},\n", frame.cell, duration));
+                i += 1;
+
+                remaining -= duration;
+                if remaining == 0 {break;}
+            }
         }
 
         export.push_str("    /* End */ END_ANIMATION\n};\n\n");
@@ -1,7 +1,9 @@
 use std::{collections::HashMap, fs};
 
-use crate::{anim_parser::{Animation, AnimationCel}, ProjectStructure};
+use crate::{anim_parser::{Animation, AnimationCel, ParseError}, project::ProjectStructure};
 
+// Reader for the old .yan format, see create_project_bin
+#[allow(dead_code)]
 pub fn load_project(path_str: &str) -> (HashMap<String, AnimationCel>, Vec<Animation>) {
     let project_bytes = fs::read(path_str).unwrap();
 
@@ -32,7 +34,7 @@ pub fn load_project(path_str: &str) -> (HashMap<String, AnimationCel>, Vec<Anima
         if read_name {
             current_end_index = current_start_index + name_length + project_bytes[i] as usize * 8 + 1;
             
-            let cell = AnimationCel::from_bin(&project_bytes[current_start_index..current_end_index]);
+            let cell = AnimationCel::from_bin_opt(&project_bytes[current_start_index..current_end_index]);
             if let Some(cell) = cell {
                 animation_cels.insert(cell.name.clone(), cell);
             }
@@ -69,7 +71,7 @@ pub fn load_project(path_str: &str) -> (HashMap<String, AnimationCel>, Vec<Anima
             
             current_end_index = current_start_index + name_length + animation_length + 2;
             
-            let animation = Animation::from_bin(&project_bytes[current_start_index..current_end_index]);
+            let animation = Animation::from_bin_opt(&project_bytes[current_start_index..current_end_index]);
             
             if let Some(animation) = animation {
                 animations.push(animation);
@@ -92,11 +94,10 @@ pub fn load_project_json(path_str: &str) -> Result<(HashMap<String, AnimationCel
 
     for animation in &mut project.animations {
         animation.duration = animation.get_total_frames();
-        
-        let mut i = 0;
-        for frame in &mut animation.frames {
-            frame.id = i;
-            i += 1;
+
+        // Frame ids aren't saved, so every frame comes back with the same one
+        if animation.has_duplicate_ids() {
+            animation.reindex_frames();
         }
     }
 
@@ -126,7 +127,12 @@ pub fn load_animation_cels_from_c(path_str: &str) -> HashMap<String, AnimationCe
             let cel_str_end = sliced_cel[cel_str_start..].find(';')?;
             let cel_str = &sliced_cel[cel_str_start..cel_str_start + cel_str_end];
 
-            AnimationCel::from_c(cel_str, cel_name)
+            // The AnimationCel declaration already shows this is the right format, so no words is just an empty cel
+            match AnimationCel::from_c(cel_str, cel_name) {
+                Ok(cel) => Some(cel),
+                Err(ParseError::NoWords) => Some(AnimationCel { name: cel_name.to_string(), oams: Vec::new() }),
+                Err(_) => None
+            }
         })
         .map(|cel| (cel.name.clone(), cel))
         .collect()
@@ -154,7 +160,7 @@ pub fn load_animations_from_c(path_str: &str) -> Vec<Animation> {
             let anim_str_end = sliced_anim[anim_str_start..].find(';')?;
             let anim_str = &sliced_anim[anim_str_start..anim_str_start + anim_str_end];
             
-            Animation::from_c(&anim_str, &anim_name)
+            Animation::from_c_opt(anim_str, anim_name)
         })
         .collect()
 }
\ No newline at end of file
@@ -0,0 +1,8 @@
+// Parsing and drawing of the GBA formats and the project data around them, split out of the app so the benches
+// and other tools can use them
+pub mod palette_parser;
+pub mod sprite_parser;
+pub mod anim_parser;
+pub mod scrubber;
+pub mod cel_library;
+pub mod project;
@@ -1,25 +1,25 @@
-use std::time::Instant;
-use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use eframe::egui;
-use egui::{Rect, TextureHandle, Visuals};
+use egui::TextureHandle;
 use egui_extras::install_image_loaders;
+use yanimator::{anim_parser, cel_library, palette_parser, project, sprite_parser};
+use cel_library::CelLibrary;
 use palette_parser::Palette;
 use panels::{animation_cells::AnimationCellsPanel, timeline::Timeline};
-use serde::{Deserialize, Serialize};
 use sprite_parser::Spritesheet;
-use anim_parser::{Animation, AnimationCel};
+use anim_parser::Animation;
 
 use crate::panels::{topbar::Topbar, viewport::Viewport};
 
-mod palette_parser;
-mod sprite_parser;
-mod anim_parser;
 mod export;
 mod panels;
 mod import;
 
 fn main() -> eframe::Result {
+    // Warnings by default so clamped frame durations and the like show up without setting RUST_LOG
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
     let native_options = eframe::NativeOptions::default();
     eframe::run_native("Yanimator", native_options, Box::new(|cc| Ok(Box::new(Yanimator::new(cc)))))
 }
@@ -40,7 +40,7 @@ struct Yanimator {
     
     editing_cell: String,
     editing_oam: usize,
-    animation_cels: HashMap<String, AnimationCel>,
+    animation_cels: CelLibrary,
     
 
     animations: Vec<Animation>,
@@ -55,18 +55,12 @@ struct Yanimator {
     viewport: Viewport
 }
 
-#[derive(Deserialize, Serialize)]
-struct ProjectStructure {
-    animation_cels: HashMap<String, AnimationCel>,
-    animations: Vec<Animation>
-}
-
 impl Yanimator {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
         let textures = Vec::new();
         let spritesheet = Spritesheet { sprites: Vec::new() };
         let palette = Palette { palettes: Vec::new() };
-        let animation_cels = HashMap::new();
+        let animation_cels = CelLibrary::new();
         let animations = Vec::new();
 
         Self {
@@ -98,19 +92,31 @@ impl eframe::App for Yanimator {
         let now = Instant::now();
         let elapsed = now.duration_since(self.last_frame_time).as_secs_f32();
         
-        if elapsed >= 1.0 / 60.0 {
-            if self.timeline.playing {self.frames += 1;}
+        // Ticks come at the preview speed, which never touches the frame durations
+        let tick_rate = self.animations.get(self.animation_id).map_or(60.0, |animation| animation.tick_rate() * animation.speed.max(0.0));
+        
+        if !self.timeline.playing || tick_rate <= 0.0 {
             self.last_frame_time = now;
+        } else {
+            // Fast speeds can owe several ticks in one repaint. Only the time those ticks took is used up,
+            // so the rest carries over and playback stays on the scaled rate
+            let ticks = (elapsed * tick_rate) as usize;
+
+            if ticks > 0 {
+                self.frames += ticks;
+                self.last_frame_time += Duration::from_secs_f32(ticks as f32 / tick_rate);
+            }
         }
         
         let animation = self.animations.get_mut(self.animation_id);
         
         if let Some(animation) = animation {
-            if self.frames >= animation.get_total_frames() {
-                self.frames = 0;
+            // Playback loops, but a playhead moved past the end while paused stays on the last frame
+            if self.timeline.playing && self.frames >= animation.get_total_frames() {
+                self.frames = self.frames.checked_rem(animation.get_total_frames()).unwrap_or(0);
             }
             
-            animation.current_frame = animation.get_anim_frame_from_frames(self.frames);
+            animation.current_frame = animation.frame_index_at_tick_clamped(self.frames);
         }
         
         ctx.request_repaint();
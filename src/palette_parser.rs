@@ -11,6 +11,18 @@ pub struct Palette {
     pub palettes: Vec<Vec<RGB>>
 }
 
+// 16 colors per palette, the last one can come up short
+fn split_into_palettes(colors: Vec<RGB>) -> Vec<Vec<RGB>> {
+    let mut palettes = Vec::new();
+    let mut colors = colors.into_iter().peekable();
+
+    while colors.peek().is_some() {
+        palettes.push(colors.by_ref().take(0x10).collect());
+    }
+
+    palettes
+}
+
 impl Palette {
     pub fn from_pal(file_path: &str) -> Result<Palette, std::io::Error> {
         let bytes = fs::read(file_path)?;
@@ -36,4 +48,78 @@ impl Palette {
             palettes
         })
     }
+
+    // Raw GBA palette RAM, 16 little-endian BGR555 colors per palette
+    pub fn from_gba_bytes(bytes: &[u8]) -> Palette {
+        let colors: Vec<RGB> = bytes.chunks_exact(2).map(|color| {
+            let value = (color[0] as u16) | ((color[1] as u16) << 8);
+
+            // Copying the top bits into the bottom ones makes 0x1F come out as 0xFF
+            let expand = |channel: u16| ((channel << 3) | (channel >> 2)) as u8;
+
+            RGB {
+                r: expand(value & 0x1F),
+                g: expand((value >> 5) & 0x1F),
+                b: expand((value >> 10) & 0x1F)
+            }
+        }).collect();
+
+        let palettes = split_into_palettes(colors);
+
+        Palette {
+            palettes
+        }
+    }
+
+    // Text palette: a JASC-PAL header, a version line, the color count and then one "r g b" line per color
+    pub fn from_jasc_pal(text: &str) -> Result<Palette, std::io::Error> {
+        let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+        let mut lines = text.lines().map(|line| line.trim());
+
+        if lines.next() != Some("JASC-PAL") {
+            return Err(invalid("missing JASC-PAL header"));
+        }
+
+        lines.next();
+
+        let count: usize = match lines.next().and_then(|line| line.parse().ok()) {
+            Some(count) => count,
+            None => return Err(invalid("missing color count"))
+        };
+
+        let mut colors = Vec::new();
+
+        for line in lines.take(count) {
+            let channels: Vec<u8> = line.split_whitespace().filter_map(|channel| channel.parse().ok()).collect();
+
+            if channels.len() != 3 {
+                return Err(invalid(&format!("invalid color \"{}\"", line)));
+            }
+
+            colors.push(RGB {r: channels[0], g: channels[1], b: channels[2]});
+        }
+
+        if colors.len() != count {
+            return Err(invalid("fewer colors than the header says"));
+        }
+
+        let palettes = split_into_palettes(colors);
+
+        Ok(Palette {
+            palettes
+        })
+    }
+
+    // Picks the parser from the file contents, anything that isn't RIFF or JASC-PAL is read as raw GBA palette data
+    pub fn from_file(file_path: &str) -> Result<Palette, std::io::Error> {
+        let bytes = fs::read(file_path)?;
+
+        if bytes.starts_with(b"RIFF") {
+            Palette::from_pal(file_path)
+        } else if bytes.starts_with(b"JASC-PAL") {
+            Palette::from_jasc_pal(&String::from_utf8_lossy(&bytes))
+        } else {
+            Ok(Palette::from_gba_bytes(&bytes))
+        }
+    }
 }
\ No newline at end of file
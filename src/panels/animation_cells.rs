@@ -1,13 +1,15 @@
-use egui::{include_image, pos2, vec2, Button, Frame, Id, ImageButton, Modal, Rect, Scene, Sense, Ui};
+use egui::{include_image, vec2, Button, Id, ImageButton, Modal, Rect, Scene, Sense, Ui};
 use itertools::Itertools;
 
-use crate::{anim_parser::AnimationCel, AppState, Yanimator};
+use crate::{anim_parser::{AnimationCel, DrawOptions}, AppState, Yanimator};
 
 pub struct AnimationCellsPanel {
     pub creation_modal_open: bool,
     deletion_confirmation_modal_open: bool,
     cell_name: String,
-    deleting_cell: Option<String>
+    deleting_cell: Option<String>,
+    // Cel the rename modal is open for
+    pub renaming_cell: Option<String>
 }
 
 impl AnimationCellsPanel {
@@ -16,7 +18,8 @@ impl AnimationCellsPanel {
             creation_modal_open: false,
             deletion_confirmation_modal_open: false,
             cell_name: String::from(""),
-            deleting_cell: None
+            deleting_cell: None,
+            renaming_cell: None
         }
     }
 }
@@ -24,10 +27,10 @@ impl AnimationCellsPanel {
 fn is_cell_name_invalid(app: &mut Yanimator) -> Option<String> {
     let cell_name = &app.animation_cells_panel.cell_name;
     
-    if cell_name.len() == 0 {return None}
+    if cell_name.is_empty() {return None}
     if cell_name.contains(" ") {return Some(String::from("Cell name must not contain spaces"))}
-    if !cell_name.chars().nth(0).unwrap().is_alphabetic() {return Some(String::from("First character in cell name must be a letter"))}
-    if app.animation_cels.get(cell_name).is_some() {return Some(String::from("Cell name has already been used"))}
+    if !cell_name.chars().next().unwrap().is_alphabetic() {return Some(String::from("First character in cell name must be a letter"))}
+    if app.animation_cels.contains(cell_name) {return Some(String::from("Cell name has already been used"))}
 
     None
 }
@@ -35,18 +38,29 @@ fn is_cell_name_invalid(app: &mut Yanimator) -> Option<String> {
 fn create_animation_cell(app: &mut Yanimator) {
     app.animation_cells_panel.creation_modal_open = false;
 
-    app.animation_cels.insert(app.animation_cells_panel.cell_name.clone(), AnimationCel {
+    app.animation_cels.insert(AnimationCel {
         name: app.animation_cells_panel.cell_name.clone(),
         oams: Vec::new()
     });
 }
 
+// Frames showing the cel follow it to the new name
+fn rename_animation_cell(app: &mut Yanimator) {
+    if let Some(renaming_cell) = app.animation_cells_panel.renaming_cell.take() {
+        let new_name = app.animation_cells_panel.cell_name.clone();
+
+        if app.animation_cels.rename(&renaming_cell, &new_name, &mut app.animations) && app.editing_cell == renaming_cell {
+            app.editing_cell = new_name;
+        }
+    }
+}
+
 fn remove_animation_cell(app: &mut Yanimator) {
     if let Some(deleting_cell) = &app.animation_cells_panel.deleting_cell {
         app.animation_cels.remove(deleting_cell);
         
         for animation in &mut app.animations {
-            let mut removal_indexes: Vec<usize> = animation.frames.iter().enumerate().filter_map(|(_, frame)| {
+            let mut removal_indexes: Vec<usize> = animation.frames.iter().filter_map(|frame| {
                 if frame.cell == *deleting_cell {
                     Some(frame.id)
                 } else {None}
@@ -108,10 +122,16 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
         .striped(true)
         .spacing([40.0, 4.0])
         .show(ui, |ui| {*/
-            for name in app.animation_cels.keys().sorted() {
+            // Every row's thumbnail is collected and drawn together once the list is laid out
+            let mut thumbnails = Vec::new();
+
+            for name in app.animation_cels.names().sorted() {
                 let cel = app.animation_cels.get(name).unwrap();
 
                 ui.horizontal(|ui| {
+                    let (thumbnail, _) = ui.allocate_exact_size(vec2(20.0, 20.0), Sense::hover());
+                    thumbnails.push((cel, thumbnail));
+
                     let button = ui.add(Button::new(name).min_size(vec2(ui.available_width(), 20.0)));
                     
                     if button.double_clicked() {
@@ -126,6 +146,12 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
                             ui.close_menu();
                         }
 
+                        if ui.add(Button::image_and_text(include_image!("../../assets/edit.png"), "Rename")).clicked() {
+                            app.animation_cells_panel.renaming_cell = Some(String::from(name));
+                            app.animation_cells_panel.cell_name = String::from(name);
+                            ui.close_menu();
+                        }
+
                         if ui.add(Button::image_and_text(include_image!("../../assets/delete.png"), "Delete")).clicked() {
                             app.animation_cells_panel.deleting_cell = Some(String::from(name));
                             app.animation_cells_panel.deletion_confirmation_modal_open = true;
@@ -147,7 +173,7 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
                             Scene::default()
                                 .zoom_range(0.5..=0.5)
                                 .show(ui, &mut rect, |ui| {
-                                    cel.draw(&app.textures, ui, false);
+                                    cel.draw(&app.textures, ui, DrawOptions::default());
                                 });
                         });
                     });
@@ -170,6 +196,8 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
                     });*/
                 });
             }
+
+            AnimationCel::draw_batched(&thumbnails, &app.textures, ui);
         //});
         
     });
@@ -187,9 +215,8 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
             ui.label("Cell Name:");
             let field = ui.text_edit_singleline(&mut app.animation_cells_panel.cell_name);
             
-            match is_cell_name_invalid(app) {
-                Some(message) => field.show_tooltip_text(message),
-                None => {}
+            if let Some(message) = is_cell_name_invalid(app) {
+                field.show_tooltip_text(message);
             }
 
             ui.horizontal(|ui| {
@@ -197,11 +224,36 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
                     app.animation_cells_panel.creation_modal_open = false;
                 }
 
-                if ui.button("Create").clicked() && app.animation_cells_panel.cell_name.len() > 0 && is_cell_name_invalid(app).is_none() {
+                if ui.button("Create").clicked() && !app.animation_cells_panel.cell_name.is_empty() && is_cell_name_invalid(app).is_none() {
                     create_animation_cell(app)
                 }
             });
         });
+    } else if let Some(renaming_cell) = app.animation_cells_panel.renaming_cell.clone() {
+        Modal::new(Id::new("animation_cell_rename")).show(ui.ctx(), |ui| {
+            ui.heading(format!("Renaming {}", renaming_cell));
+            ui.separator();
+
+            ui.label("Cell Name:");
+            let field = ui.text_edit_singleline(&mut app.animation_cells_panel.cell_name);
+
+            let unchanged = app.animation_cells_panel.cell_name == renaming_cell;
+            let invalid = if unchanged {None} else {is_cell_name_invalid(app)};
+
+            if let Some(message) = &invalid {
+                field.show_tooltip_text(message);
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Cancel").clicked() {
+                    app.animation_cells_panel.renaming_cell = None;
+                }
+
+                if ui.button("Rename").clicked() && !unchanged && !app.animation_cells_panel.cell_name.is_empty() && invalid.is_none() {
+                    rename_animation_cell(app);
+                }
+            });
+        });
     } else if app.animation_cells_panel.deletion_confirmation_modal_open {
         Modal::new(Id::new("animation_cell_deletion")).show(ui.ctx(), |ui| {
             if let Some(deleting_cell) = &app.animation_cells_panel.deleting_cell {
@@ -1,10 +1,10 @@
 use std::{path::PathBuf};
 
-use egui::{include_image, menu, Button, ColorImage, Key, KeyboardShortcut, Modifiers, TextureHandle, Ui};
+use egui::{include_image, menu, Button, Key, KeyboardShortcut, Modifiers, TextureHandle, Ui};
 
 
-use crate::{export, import, palette_parser::Palette, sprite_parser::Spritesheet, Yanimator};
-use rfd::FileDialog;
+use crate::{cel_library::CelLibrary, export, import, palette_parser::Palette, project::ProjectStructure, sprite_parser::Spritesheet, Yanimator};
+use rfd::{FileDialog, MessageButtons, MessageDialog, MessageLevel};
 
 const NEW_PROJECT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::N);
 const OPEN_PROJECT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::O);
@@ -27,7 +27,7 @@ fn open_project(app: &mut Yanimator) {
     
     if let Ok((animation_cels, animations)) = import::load_project_json(path_str) {
         app.animations = animations;
-        app.animation_cels = animation_cels;
+        app.animation_cels = CelLibrary::from_map(animation_cels);
     }
 
     //let (animation_cels, animations) = import::load_project(path_str);
@@ -50,12 +50,12 @@ fn save_project(app: &mut Yanimator) {
         None => return
     };
     
-    crate::export::create_project_json(path_str, &app.animation_cels, &app.animations);
+    crate::export::create_project_json(path_str, app.animation_cels.as_map(), &app.animations);
 }
 
 fn load_palette(ui: &mut Ui, app: &mut Yanimator) {
     let file_path: PathBuf = match FileDialog::new()
-    .add_filter("Palette", &["pal"])
+    .add_filter("Palette", &["pal", "gbapal"])
     .set_directory("/")
     .set_title("Select a palette")
     .pick_file() {
@@ -68,7 +68,7 @@ fn load_palette(ui: &mut Ui, app: &mut Yanimator) {
         None => return
     };
 
-    app.palette = Palette::from_pal(path_str).unwrap();
+    app.palette = Palette::from_file(path_str).unwrap();
     load_texture_handles(ui, app);
 }
 
@@ -94,47 +94,11 @@ fn load_spritesheet(ui: &mut Ui, app: &mut Yanimator) {
 fn load_texture_handles(ui: &mut Ui, app: &mut Yanimator) {
     let mut textures: Vec<Vec<TextureHandle>> = Vec::new();
     
-    if app.palette.palettes.len() == 0 {return;}
-    if app.spritesheet.sprites.len() == 0 {return;}
+    if app.palette.palettes.is_empty() {return;}
+    if app.spritesheet.sprites.is_empty() {return;}
     
     for pal in app.palette.palettes.iter() {
-        let mut palette_textures = Vec::new();
-
-        for i in 0..app.spritesheet.sprites.len() {
-            let sprite = &app.spritesheet.sprites[i];
-            let mut pixels: Vec<u8> = Vec::new();
-        
-            for i in 0..0x40 {
-                let palette_id = sprite.pixels[i];
-                
-                if palette_id == 0 {
-                    pixels.push(0);
-                    pixels.push(0);
-                    pixels.push(0);
-                    pixels.push(0);
-                } else {
-                    let rgb = &pal[palette_id as usize];
-                    pixels.push(rgb.r);
-                    pixels.push(rgb.g);
-                    pixels.push(rgb.b);
-                    pixels.push(255);
-                }
-            }
-            
-            palette_textures.push(
-                ui.ctx().load_texture(
-                i.to_string(),
-                ColorImage::from_rgba_unmultiplied([8, 8], &pixels), 
-                egui::TextureOptions {
-                    magnification: egui::TextureFilter::Nearest,
-                    minification: egui::TextureFilter::Nearest,
-                    wrap_mode: egui::TextureWrapMode::Repeat,
-                    mipmap_mode: None,
-                })
-            )
-        }
-
-        textures.push(palette_textures);
+        textures.push(app.spritesheet.to_textures(ui.ctx(), pal));
     }
 
     app.textures = textures;
@@ -155,7 +119,7 @@ fn load_animation_cels(app: &mut Yanimator) {
         None => return
     };
 
-    app.animation_cels = import::load_animation_cels_from_c(path_str);
+    app.animation_cels = CelLibrary::from_map(import::load_animation_cels_from_c(path_str));
 }
 
 fn load_animations(app: &mut Yanimator) {
@@ -176,6 +140,32 @@ fn load_animations(app: &mut Yanimator) {
     app.animations = import::load_animations_from_c(path_str);
 }
 
+fn import_bin_folder(app: &mut Yanimator) {
+    let dir: PathBuf = match FileDialog::new()
+    .set_directory("/")
+    .set_title("Select a folder of .bin cels and animations")
+    .pick_folder() {
+        Some(dir) => dir,
+        None => return
+    };
+
+    let (project, errors) = ProjectStructure::import_bin_dir(&dir);
+
+    if !errors.is_empty() {
+        let skipped: Vec<String> = errors.iter().map(|(path, error)| format!("{}: {}", path.display(), error)).collect();
+
+        MessageDialog::new()
+        .set_level(MessageLevel::Warning)
+        .set_title("Some files were skipped")
+        .set_description(skipped.join("\n"))
+        .set_buttons(MessageButtons::Ok)
+        .show();
+    }
+
+    app.animation_cels = CelLibrary::from_map(project.animation_cels);
+    app.animations = project.animations;
+}
+
 fn export_animation_cels(app: &mut Yanimator) {
     let file_path: PathBuf = match FileDialog::new()
     .add_filter("C", &["c"])
@@ -191,7 +181,7 @@ fn export_animation_cels(app: &mut Yanimator) {
         None => return
     };
 
-    export::export_animation_cels(path_str, &app.animation_cels);
+    export::export_animation_cels(path_str, app.animation_cels.as_map());
 }
 
 fn export_animations(app: &mut Yanimator) {
@@ -212,6 +202,55 @@ fn export_animations(app: &mut Yanimator) {
     export::export_animations(path_str, &app.animations);
 }
 
+fn export_animation_strip(app: &mut Yanimator) {
+    let animation = match app.animations.get(app.animation_id) {
+        Some(animation) => animation,
+        None => return
+    };
+
+    let file_path: PathBuf = match FileDialog::new()
+    .add_filter("PNG", &["png"])
+    .set_directory("/")
+    .set_title("Select export location")
+    .save_file() {
+        Some(file) => file,
+        None => return
+    };
+
+    let path_str = match file_path.to_str() {
+        Some(path) => path,
+        None => return
+    };
+
+    let _ = animation.export_strip(app.animation_cels.as_map(), &app.spritesheet, &app.palette, 1, path_str);
+}
+
+fn export_animation_atlas(app: &mut Yanimator) {
+    let animation = match app.animations.get(app.animation_id) {
+        Some(animation) => animation,
+        None => return
+    };
+
+    let file_path: PathBuf = match FileDialog::new()
+    .add_filter("PNG", &["png"])
+    .set_directory("/")
+    .set_title("Select export location")
+    .save_file() {
+        Some(file) => file,
+        None => return
+    };
+
+    // The frames JSON goes next to the image with the same name
+    let json_path = file_path.with_extension("json");
+
+    let (path_str, json_path_str) = match (file_path.to_str(), json_path.to_str()) {
+        (Some(path), Some(json_path)) => (path, json_path),
+        _ => return
+    };
+
+    let _ = animation.export_atlas(app.animation_cels.as_map(), &app.spritesheet, &app.palette, 1, path_str, json_path_str);
+}
+
 pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
     if ui.input_mut(|i| i.consume_shortcut(&OPEN_PROJECT)) {
         open_project(app);
@@ -252,6 +291,10 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
             if ui.add(Button::image_and_text(include_image!("../../assets/television.png"), "Load Animations (.c)")).clicked() {
                 load_animations(app);
             }
+
+            if ui.add(Button::image_and_text(include_image!("../../assets/folder_page.png"), "Import .bin Folder")).clicked() {
+                import_bin_folder(app);
+            }
             
             ui.separator();
 
@@ -262,6 +305,14 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
             if ui.add(Button::image_and_text(include_image!("../../assets/film_save.png"), "Export Animations (.c)")).clicked() {
                 export_animations(app);
             }
+
+            if ui.add(Button::image_and_text(include_image!("../../assets/film_save.png"), "Export Animation Strip (.png)")).clicked() {
+                export_animation_strip(app);
+            }
+
+            if ui.add(Button::image_and_text(include_image!("../../assets/film_save.png"), "Export Animation Atlas (.png + .json)")).clicked() {
+                export_animation_atlas(app);
+            }
         });
     });
 }
\ No newline at end of file
@@ -5,4 +5,4 @@ pub mod oams;
 pub mod properties;
 pub mod viewport;
 pub mod spritesheet;
-pub mod menu_bar;
\ No newline at end of file
+pub mod menu_bar;
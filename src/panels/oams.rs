@@ -1,6 +1,6 @@
 use egui::{include_image, vec2, ImageButton, Ui};
 
-use crate::{anim_parser::{OAMFlip, OAMShape, OAMSize, OAM}, Yanimator};
+use crate::{anim_parser::{OAMSize, OAM}, Yanimator};
 
 pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
     let cell = match app.animation_cels.get_mut(&app.editing_cell) {
@@ -14,15 +14,9 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
             if ui.add_sized(vec2(20.0, 20.0), ImageButton::new(include_image!("../../assets/add.png"))).clicked() {
                 cell.oams.push(OAM {
-                    shape: OAMShape::Square,
                     size: OAMSize::Size0,
-                    flip: OAMFlip::None,
-                    x: 0,
-                    y: 0,
-                    palette: 0,
-                    tile: 0,
-                    selected: false,
-                    zindex: cell.oams.len()
+                    zindex: cell.oams.len(),
+                    ..Default::default()
                 });
             }
         });
@@ -35,14 +29,11 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
         .striped(true)
         .spacing([40.0, 4.0])
         .show(ui, |ui| {
-            let mut i = 0;
-            
-            for _oam in &cell.oams {
+            for i in 0..cell.oams.len() {
                 if ui.button(format!("OAM_{}", i)).clicked() {
                     app.editing_oam = i;
                 }
                 
-                i += 1;
                 ui.end_row();
             }
         });
@@ -1,6 +1,6 @@
 use egui::{InputState, Key, Ui};
 
-use crate::{anim_parser::{OAMFlip, OAMShape, OAMSize}, Yanimator};
+use crate::{anim_parser::{OAMFlip, OAMShape, OAMSize, MAX_PALETTE, OAM_X_RANGE, OAM_Y_RANGE}, Yanimator};
 
 fn get_size_string_with_shape<'a>(size: &'a OAMSize, shape: &'a OAMShape) -> &'a str {
     match shape {
@@ -65,11 +65,11 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
         .show(ui, |ui| {
         
             ui.label("X");
-            ui.add(egui::DragValue::new(&mut oam.x).speed(0.2));
+            ui.add(egui::DragValue::new(&mut oam.x).speed(0.2).range(OAM_X_RANGE));
             ui.end_row();
 
             ui.label("Y");
-            ui.add(egui::DragValue::new(&mut oam.y).speed(0.2));
+            ui.add(egui::DragValue::new(&mut oam.y).speed(0.2).range(OAM_Y_RANGE));
             ui.end_row();
         
             ui.label("Tile ID");
@@ -81,13 +81,9 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
             // the world would be a perfect place
             // if these drag value ranged didnt have to be inclusive
             
-            let mut upper_range = app.palette.palettes.len();
+            let upper_range = app.palette.palettes.len().saturating_sub(1);
             
-            if upper_range > 0 {
-                upper_range -= 1;
-            }
-            
-            ui.add(egui::DragValue::new(&mut oam.palette).speed(0.2).range(0..=upper_range));
+            ui.add(egui::DragValue::new(&mut oam.palette).speed(0.2).range(0..=upper_range.min(MAX_PALETTE)));
             ui.end_row();
             
             ui.label("Shape");
@@ -102,7 +98,7 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
 
             ui.label("Size");
             egui::ComboBox::from_id_salt("size_dropdown")
-                .selected_text(format!("{}", get_size_string_with_shape(&oam.size, &oam.shape)))
+                .selected_text(get_size_string_with_shape(&oam.size, &oam.shape))
                 .show_ui(ui, |ui| {
                     ui.selectable_value(&mut oam.size, OAMSize::Size0, get_size_string_with_shape(&OAMSize::Size0, &oam.shape));
                     ui.selectable_value(&mut oam.size, OAMSize::Size1, get_size_string_with_shape(&OAMSize::Size1, &oam.shape));
@@ -125,10 +121,27 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
             ui.label("Z-Index");
             ui.add(egui::DragValue::new(&mut oam.zindex).speed(0.2).range(0..=oam_len));
             ui.end_row();
+
+            ui.label("Enabled");
+            ui.checkbox(&mut oam.enabled, "");
+            ui.end_row();
+
+            // An empty name takes the OAM out of its layer
+            ui.label("Layer");
+            let mut layer = oam.layer.clone().unwrap_or_default();
+            if ui.text_edit_singleline(&mut layer).changed() {
+                oam.layer = if layer.is_empty() { None } else { Some(layer) };
+            }
+            ui.end_row();
         });
 
     ui.separator();
 
+    let (preview_rect, _) = ui.allocate_exact_size(egui::vec2(64.0, 64.0), egui::Sense::hover());
+    oam.draw_isolated(&app.textures, ui, preview_rect);
+
+    ui.separator();
+
     if ui.button("Delete OAM").clicked() {
         remove_oam(app);
     }
@@ -167,4 +180,8 @@ pub fn input(input: &InputState, app: &mut Yanimator) {
     if input.key_pressed(Key::ArrowRight) {
         oam.x += 1;
     }
+
+    if app.viewport.keep_on_screen_enabled {
+        oam.clamp_to_screen();
+    }
 }
\ No newline at end of file
@@ -23,11 +23,7 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
     ui.label("Spritesheet");
     ui.horizontal(|ui| {
         ui.label("Preview Palette ID");
-        let mut upper_range = app.palette.palettes.len();
-            
-        if upper_range > 0 {
-            upper_range -= 1;
-        }
+        let upper_range = app.palette.palettes.len().saturating_sub(1);
         ui.add(egui::DragValue::new(&mut app.spritesheet_palette).speed(0.2).range(0..=upper_range));
     });
     
@@ -1,6 +1,6 @@
 use egui::{include_image, pos2, vec2, Color32, Image, ImageButton, InputState, Key, PointerButton, Rect, Response, Scene, Stroke, Ui};
 
-use crate::Yanimator;
+use crate::{anim_parser::DrawOptions, Yanimator};
 
 
 #[derive(Debug)]
@@ -40,7 +40,7 @@ impl Timeline {
                 keyframe.input_rect = input_rect;
             },
             None => {
-                self.keyframes.push(Keyframe { input_rect: input_rect, selected: false, hovered: false, id: frame_id });
+                self.keyframes.push(Keyframe { input_rect, selected: false, hovered: false, id: frame_id });
                 
             }
         }
@@ -126,8 +126,8 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
     let height = ui.available_height();
 
     ui.horizontal(|ui| {
-        if ui.add(ImageButton::new(include_image!("../../assets/frame_left.png"))).clicked() {
-            if app.frames > 0 { app.frames -= 1 };
+        if ui.add(ImageButton::new(include_image!("../../assets/frame_left.png"))).clicked() && app.frames > 0 {
+            app.frames -= 1;
         };
         if ui.add(ImageButton::new(
             if app.timeline.playing {
@@ -155,9 +155,18 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
             let minimum_duration = animation.get_minimum_duration();
             
             ui.label("Animation Duration: ");
-            ui.add(egui::DragValue::new(&mut animation.duration).range(minimum_duration..=minimum_duration + 255));
+            ui.add(egui::DragValue::new(&mut animation.duration).range(minimum_duration..=minimum_duration + u16::MAX as usize));
 
             animation.update_duration();
+
+            if ui.button("Trim").on_hover_text("Remove empty frames at the start and end of the animation").clicked() {
+                animation.trim(app.animation_cels.as_map());
+                app.timeline.keyframes.clear();
+            }
+
+            ui.label("Speed: ");
+            ui.add(egui::DragValue::new(&mut animation.speed).speed(0.05).range(0.1..=4.0).suffix("x"))
+                .on_hover_text("Preview playback speed, the frame durations stay the same");
         }
     });
 
@@ -221,6 +230,11 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
 
         let animation = app.animations.get_mut(app.animation_id);
         if let Some(animation) = animation {
+            // Keyframes are matched to frames by id, so repair before handing the ids out
+            if animation.has_duplicate_ids() {
+                animation.reindex_frames();
+            }
+
             for frame in &animation.frames {
                 let keyframe = draw_keyframe(ui, height, &mut app.timeline, pos, frame.id);
                 let cel = app.animation_cels.get(&frame.cell);
@@ -234,7 +248,7 @@ pub fn ui(ui: &mut Ui, app: &mut Yanimator) {
                             Scene::default()
                                 .zoom_range(0.5..=0.5)
                                 .show(ui, &mut rect, |ui| {
-                                    cel.draw(&app.textures, ui, false);
+                                    cel.draw(&app.textures, ui, DrawOptions::default());
                                 });
                         });
                     });
@@ -252,28 +266,23 @@ pub fn input(input: &InputState, app: &mut Yanimator) {
     let mouse_pos = input.pointer.latest_pos().unwrap_or(pos2(0.0, 0.0));
     
     for event in input.events.clone() {
-        match event {
-            egui::Event::MouseWheel { unit: _, delta, modifiers: _ } => {
-                if app.timeline.rect.contains(mouse_pos) {
-                    if input.modifiers.ctrl {
-                        app.timeline.zoom += delta.y;
-
-                        if app.timeline.zoom < 3.0 {
-                            app.timeline.zoom = 3.0;
-                        }
-                    } else {
-                        app.timeline.scroll += delta.y * SCROLL_SPEED;
+        if let egui::Event::MouseWheel { unit: _, delta, modifiers: _ } = event {
+            if app.timeline.rect.contains(mouse_pos) {
+                if input.modifiers.ctrl {
+                    app.timeline.zoom += delta.y;
+
+                    if app.timeline.zoom < 3.0 {
+                        app.timeline.zoom = 3.0;
                     }
+                } else {
+                    app.timeline.scroll += delta.y * SCROLL_SPEED;
                 }
-            },
-            _ => {}
+            }
         }
     }
     
-    if input.pointer.button_down(PointerButton::Middle) {
-        if app.timeline.rect.contains(mouse_pos) {
-            app.frames = ((mouse_pos.x - app.timeline.scroll - KEYFRAME_SIZE / 2.0) / app.timeline.zoom) as usize;
-        }
+    if input.pointer.button_down(PointerButton::Middle) && app.timeline.rect.contains(mouse_pos) {
+        app.frames = ((mouse_pos.x - app.timeline.scroll - KEYFRAME_SIZE / 2.0) / app.timeline.zoom) as usize;
     }
 
     let mut deselect_others = None;
@@ -326,7 +335,7 @@ pub fn input(input: &InputState, app: &mut Yanimator) {
         app.timeline.dragging = false;
     }
 
-    if !any_hovered && app.timeline.dragging == false && (input.pointer.button_down(PointerButton::Primary) || input.pointer.button_down(PointerButton::Secondary)) {
+    if !any_hovered && !app.timeline.dragging && (input.pointer.button_down(PointerButton::Primary) || input.pointer.button_down(PointerButton::Secondary)) {
         for keyframe in &mut app.timeline.keyframes {
             keyframe.selected = false;
         }
@@ -335,17 +344,29 @@ pub fn input(input: &InputState, app: &mut Yanimator) {
     // todo: there will probably be more text box modal things
     // there should be a common function to check for those
     // when more are added
-    if app.animation_cells_panel.creation_modal_open {return;}
+    if app.animation_cells_panel.creation_modal_open || app.animation_cells_panel.renaming_cell.is_some() {return;}
 
     if input.key_pressed(Key::Space) {
         app.timeline.playing = !app.timeline.playing;
     }
 
-    if input.key_pressed(Key::ArrowLeft) {
-        if app.frames > 0 { app.frames -= 1 };
+    if input.key_pressed(Key::ArrowLeft) && app.frames > 0 {
+        app.frames -= 1;
     }
 
     if input.key_pressed(Key::ArrowRight) {
         app.frames += 1;
     }
+
+    if let Some(animation) = app.animations.get(app.animation_id) {
+        if input.key_pressed(Key::OpenBracket) {
+            app.frames = animation.prev_frame_boundary(app.frames).unwrap_or(0);
+        }
+
+        if input.key_pressed(Key::CloseBracket) {
+            if let Some(boundary) = animation.next_frame_boundary(app.frames) {
+                app.frames = boundary;
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -23,9 +23,9 @@ impl Topbar {
 fn is_anim_name_invalid(app: &mut Yanimator) -> Option<String> {
     let anim_name = &app.topbar.animation_name;
     
-    if anim_name.len() == 0 {return None}
+    if anim_name.is_empty() {return None}
     if anim_name.contains(" ") {return Some(String::from("Animation name must not contain spaces"))}
-    if !anim_name.chars().nth(0).unwrap().is_alphabetic() {return Some(String::from("First character in animation name must be a letter"))}
+    if !anim_name.chars().next().unwrap().is_alphabetic() {return Some(String::from("First character in animation name must be a letter"))}
     if app.animations.iter().any(|animation| &animation.name == anim_name) {return Some(String::from("Animation name has already been used"))}
 
     None
@@ -34,12 +34,7 @@ fn is_anim_name_invalid(app: &mut Yanimator) -> Option<String> {
 fn create_animation(app: &mut Yanimator) {
     app.topbar.animation_creation_modal_open = false;
 
-    app.animations.push(Animation {
-        frames: Vec::new(),
-        name: app.topbar.animation_name.clone(),
-        current_frame: 0,
-        duration: 0
-    })
+    app.animations.push(Animation::from_frames(&app.topbar.animation_name, Vec::new()))
 }
 
 fn remove_animation(app: &mut Yanimator) {
@@ -57,9 +52,7 @@ pub fn ui_animation_editor(ui: &mut Ui, app: &mut Yanimator) {
     .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysHidden)
     .show(ui, |ui| {
         ui.horizontal(|ui| {
-            let mut i = 0;
-            
-            for animation in &mut app.animations {
+            for (i, animation) in app.animations.iter_mut().enumerate() {
                 let button = ui.button(&animation.name);
 
                 if button.clicked() {
@@ -76,8 +69,6 @@ pub fn ui_animation_editor(ui: &mut Ui, app: &mut Yanimator) {
                         ui.close_menu();
                     }
                 });
-                
-                i += 1;
             }
 
             if ui.add(Button::image_and_text(include_image!("../../assets/add.png"), "New Animation")).clicked() {
@@ -96,9 +87,8 @@ pub fn ui_animation_editor(ui: &mut Ui, app: &mut Yanimator) {
             
             let field = ui.text_edit_singleline(&mut app.topbar.animation_name);
             
-            match is_anim_name_invalid(app) {
-                Some(message) => field.show_tooltip_text(message),
-                None => {}
+            if let Some(message) = is_anim_name_invalid(app) {
+                field.show_tooltip_text(message);
             }
 
             ui.horizontal(|ui| {
@@ -106,7 +96,7 @@ pub fn ui_animation_editor(ui: &mut Ui, app: &mut Yanimator) {
                     app.topbar.animation_creation_modal_open = false;
                 }
 
-                if ui.button("Create").clicked() && app.topbar.animation_name.len() > 0 && is_anim_name_invalid(app).is_none() {
+                if ui.button("Create").clicked() && !app.topbar.animation_name.is_empty() && is_anim_name_invalid(app).is_none() {
                     create_animation(app)
                 }
             });
@@ -142,18 +132,23 @@ pub fn ui_cell_editor(ui: &mut Ui, app: &mut Yanimator) {
             if ui.add(ImageButton::new(include_image!("../../assets/back.png"))).clicked() {
                 app.state = AppState::AnimationEditor;
                 
-                for (_, cell) in &mut app.animation_cels {
+                for cell in app.animation_cels.cels_mut() {
                     for oam in &mut cell.oams {
                         oam.selected = false;
                     }
                 }
             };
             
-            ui.label(format!("{}", app.editing_cell));
+            ui.label(&app.editing_cell);
 
             ui.separator();
 
             ui.checkbox(&mut app.viewport.selection_indicator_enabled, "Toggle Selection Indicator");
+            ui.checkbox(&mut app.viewport.coordinate_gizmos_enabled, "Toggle Coordinate Gizmos");
+            ui.checkbox(&mut app.viewport.missing_placeholders_enabled, "Toggle Missing Sprite Placeholders");
+            ui.checkbox(&mut app.viewport.debug_labels_enabled, "Toggle Debug Labels");
+            ui.checkbox(&mut app.viewport.safe_area_enabled, "Toggle Screen Bounds");
+            ui.checkbox(&mut app.viewport.keep_on_screen_enabled, "Toggle Keep On Screen");
         });
     });
 }
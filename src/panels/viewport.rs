@@ -1,18 +1,27 @@
 use egui::{Rect, Scene, Ui};
-use image::flat::View;
 
-use crate::{AppState, Yanimator};
+use crate::{anim_parser::DrawOptions, AppState, Yanimator};
 
 pub struct Viewport {
     scene_rect: Rect,
-    pub selection_indicator_enabled: bool
+    pub selection_indicator_enabled: bool,
+    pub coordinate_gizmos_enabled: bool,
+    pub missing_placeholders_enabled: bool,
+    pub debug_labels_enabled: bool,
+    pub safe_area_enabled: bool,
+    pub keep_on_screen_enabled: bool
 }
 
 impl Viewport {
     pub fn init() -> Self {
         Viewport {
             scene_rect: Rect::ZERO,
-            selection_indicator_enabled: true
+            selection_indicator_enabled: true,
+            coordinate_gizmos_enabled: false,
+            missing_placeholders_enabled: false,
+            debug_labels_enabled: false,
+            safe_area_enabled: false,
+            keep_on_screen_enabled: false
         }
     }
 }
@@ -28,7 +37,7 @@ pub fn ui_animation_editor(ui: &mut Ui, app: &mut Yanimator) {
     if let Some(animation) = animation {
         if let Some(frame) = animation.frames.get(animation.current_frame) {
             if let Some(animation_cel) = app.animation_cels.get(&frame.cell) {
-                animation_cel.draw(&app.textures, ui, false);
+                animation_cel.draw(&app.textures, ui, DrawOptions::default());
             }
         }
         
@@ -46,13 +55,18 @@ pub fn ui_cell_editor(ui: &mut Ui, app: &mut Yanimator) {
     //let animation = &app.animations[app.animation_id];
 
     if let Some(animation_cel) = app.animation_cels.get_mut(&app.editing_cell) {
-        let mut i = 0;
-        for oam in &mut animation_cel.oams {
+        for (i, oam) in animation_cel.oams.iter_mut().enumerate() {
             oam.selected = app.editing_oam == i;
-            i += 1;
         }
         
-        animation_cel.draw(&app.textures, ui, app.viewport.selection_indicator_enabled);
+        animation_cel.draw(&app.textures, ui, DrawOptions {
+            selection_indicator: app.viewport.selection_indicator_enabled,
+            coordinate_gizmos: app.viewport.coordinate_gizmos_enabled,
+            missing_placeholder: app.viewport.missing_placeholders_enabled,
+            debug_labels: app.viewport.debug_labels_enabled,
+            safe_area: app.viewport.safe_area_enabled,
+            ..Default::default()
+        });
     }
 
     });
@@ -0,0 +1,136 @@
+use std::{collections::{BTreeMap, HashMap}, fs, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::anim_parser::{Animation, AnimationCel, ParseError};
+
+#[derive(Deserialize, Serialize)]
+pub struct ProjectStructure {
+    pub animation_cels: HashMap<String, AnimationCel>,
+    pub animations: Vec<Animation>
+}
+
+impl ProjectStructure {
+    // Same data as serializing the project directly, but cels and animations sorted by name and every object's
+    // keys in alphabetical order, so saving an unchanged project gives the same bytes every time
+    pub fn to_pretty_json(&self) -> Result<String, serde_json::Error> {
+        let animation_cels: BTreeMap<&String, &AnimationCel> = self.animation_cels.iter().collect();
+
+        let mut animations: Vec<&Animation> = self.animations.iter().collect();
+        animations.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // Going through Value puts the keys of every object in a sorted map
+        let value = serde_json::json!({
+            "animation_cels": animation_cels,
+            "animations": animations
+        });
+
+        serde_json::to_string_pretty(&value)
+    }
+
+    // Builds a project out of a folder of .bin files, one cel or animation per file, named after the file.
+    // Files that don't parse are returned alongside instead of stopping the import
+    pub fn import_bin_dir(dir: &Path) -> (ProjectStructure, Vec<(PathBuf, ParseError)>) {
+        let mut project = ProjectStructure { animation_cels: HashMap::new(), animations: Vec::new() };
+        let mut errors = Vec::new();
+
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|extension| extension == "bin"))
+                .collect(),
+            Err(error) => return (project, vec![(dir.to_path_buf(), error.into())])
+        };
+
+        // Sorted so the animations come out in the same order every time
+        paths.sort();
+
+        for path in paths {
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue
+            };
+
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    errors.push((path, error.into()));
+                    continue;
+                }
+            };
+
+            // Animations check their declared length, cels ignore anything past their OAMs,
+            // so an animation is tried first to keep it from being misread as a cel
+            if let Ok(mut animation) = Animation::from_bin(&bytes) {
+                animation.name = name;
+                project.animations.push(animation);
+                continue;
+            }
+
+            match AnimationCel::from_bin(&bytes) {
+                Ok(mut cel) => {
+                    cel.name = name.clone();
+                    project.animation_cels.insert(name, cel);
+                },
+                Err(error) => errors.push((path, error))
+            }
+        }
+
+        (project, errors)
+    }
+
+    // Cels that no animation frame references, sorted by name
+    pub fn unused_cels(&self) -> Vec<String> {
+        let used_cels: Vec<&String> = self.animations.iter().flat_map(|animation| animation.get_used_cels()).collect();
+
+        let mut unused_cels: Vec<String> = self.animation_cels.keys()
+            .filter(|&name| !used_cels.contains(&name))
+            .cloned()
+            .collect();
+
+        unused_cels.sort();
+        unused_cels
+    }
+
+    // Names of the cels with an OAM showing the tile, sorted
+    pub fn cels_using_tile(&self, tile: usize) -> Vec<String> {
+        let mut cels: Vec<String> = self.animation_cels.iter()
+            .filter(|(_, cel)| !cel.oams_using_tile(tile).is_empty())
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        cels.sort();
+        cels
+    }
+
+    // replace_cel_ref across every animation, returns the total number of frames changed
+    pub fn replace_cel_ref(&mut self, old: &str, new: &str) -> usize {
+        self.animations.iter_mut().map(|animation| animation.replace_cel_ref(old, new)).sum()
+    }
+
+    // Groups of cel names with identical OAMs (selection and preview state aren't compared), each group and
+    // the list sorted by name. Pair with replace_cel_ref to merge them
+    pub fn find_duplicate_cels(&self) -> Vec<Vec<String>> {
+        let mut buckets: HashMap<u64, Vec<Vec<String>>> = HashMap::new();
+
+        for (name, cel) in &self.animation_cels {
+            let groups = buckets.entry(cel.oams_hash()).or_default();
+
+            // Equal hashes can still be a collision, so a cel only joins a group whose OAMs really match
+            match groups.iter_mut().find(|group| self.animation_cels[&group[0]].oams == cel.oams) {
+                Some(group) => group.push(name.clone()),
+                None => groups.push(vec![name.clone()])
+            }
+        }
+
+        let mut duplicates: Vec<Vec<String>> = buckets.into_values().flatten().filter(|group| group.len() > 1).collect();
+
+        for group in &mut duplicates {
+            group.sort();
+        }
+        duplicates.sort();
+
+        duplicates
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use egui::{pos2, vec2, Color32, Rect, Scene, Sense, Stroke, TextureHandle, Ui};
+
+use crate::anim_parser::{Animation, AnimationCel};
+
+// Kept in egui memory so the caller doesn't have to store anything besides the animation
+#[derive(Clone, Default)]
+struct ScrubberState {
+    playing: bool,
+    elapsed: f32,
+    smooth: bool
+}
+
+// Self contained preview: the current frame, a play/pause button and a bar that can be clicked or dragged to seek
+pub fn animation_scrubber(ui: &mut Ui, animation: &mut Animation, cels: &HashMap<String, AnimationCel>, textures: &[Vec<TextureHandle>]) {
+    let id = ui.id().with("animation_scrubber").with(&animation.name);
+    let mut state: ScrubberState = ui.data(|data| data.get_temp(id)).unwrap_or_default();
+
+    let total_ticks = animation.get_total_frames();
+
+    if state.playing && total_ticks > 0 {
+        state.elapsed += ui.input(|input| input.stable_dt);
+
+        // Same rate the main timeline plays the animation at
+        let tick_length = 1.0 / animation.tick_rate();
+        let ticks = (state.elapsed / tick_length) as usize;
+        state.elapsed -= ticks as f32 * tick_length;
+
+        // advance_with_callback takes care of wrapping back to the start
+        animation.advance_with_callback(ticks, |_| {});
+        ui.ctx().request_repaint();
+    }
+
+    ui.allocate_ui(vec2(100.0, 100.0), |ui| {
+        let mut rect = Rect::ZERO;
+        Scene::default()
+            .zoom_range(0.5..=0.5)
+            .show(ui, &mut rect, |ui| {
+                // The time left over from the last whole tick puts the preview in between ticks
+                let tick_f = animation.current_tick as f32 + state.elapsed * animation.tick_rate();
+                animation.draw_scrubbed(tick_f, cels, textures, ui, state.smooth);
+            });
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button(if state.playing { "Pause" } else { "Play" }).clicked() {
+            state.playing = !state.playing;
+            state.elapsed = 0.0;
+        }
+
+        ui.checkbox(&mut state.smooth, "Smooth");
+
+        // Held frames wait for the game, this stands in for it
+        if animation.is_held() && ui.button("Release").clicked() {
+            animation.release_hold();
+        }
+
+        let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), 20.0), Sense::click_and_drag());
+        if total_ticks == 0 {return;}
+
+        let tick_width = rect.width() / total_ticks as f32;
+
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let tick = (((pointer.x - rect.min.x) / tick_width) as usize).min(total_ticks - 1);
+
+            animation.current_tick = tick;
+            animation.current_frame = animation.frame_index_at_tick_clamped(tick);
+        }
+
+        let painter = ui.painter();
+
+        for (i, segment) in animation.timeline_model(&[]).iter().enumerate() {
+            let segment_rect = Rect::from_min_size(
+                pos2(rect.min.x + segment.start as f32 * tick_width, rect.min.y),
+                vec2(segment.width as f32 * tick_width, rect.height())
+            );
+
+            let color = if i == animation.current_frame { Color32::LIGHT_BLUE } else { Color32::GRAY };
+            painter.rect_filled(segment_rect.shrink(1.0), 2, color);
+        }
+
+        let playhead_x = rect.min.x + (animation.current_tick as f32 + 0.5) * tick_width;
+        painter.line_segment([pos2(playhead_x, rect.min.y), pos2(playhead_x, rect.max.y)], Stroke::new(2.0, Color32::RED));
+    });
+
+    ui.data_mut(|data| data.insert_temp(id, state));
+}
@@ -1,4 +1,9 @@
 use std::fs;
+
+use egui::{ColorImage, Context, TextureHandle};
+
+use crate::palette_parser::RGB;
+
 pub struct Sprite {
     pub pixels: Vec<u8>
 }
@@ -6,12 +11,15 @@ pub struct Sprite {
 const LOWER_NIBBLE_MASK: u8 = 0b_00001111_u8;
 const UPPER_NIBBLE_MASK: u8 = 0b_11110000_u8;
 
+// Nearest sampling keeps pixels crisp at any zoom. Clamping instead of repeating stops flipped UVs
+// from picking up the opposite edge of the tile
+pub const PIXEL_ART_TEXTURE_OPTIONS: egui::TextureOptions = egui::TextureOptions::NEAREST;
+
 impl Sprite {
     pub fn from_4bpp(bytes: &[u8]) -> Sprite {
         let mut pixels: Vec<u8> = Vec::new();
         
-        for i in 0..0x20 {
-            let byte = bytes[i];
+        for &byte in &bytes[..0x20] {
             let left_pixel = LOWER_NIBBLE_MASK & byte;
             let right_pixel = (UPPER_NIBBLE_MASK & byte) >> 4;
 
@@ -32,6 +40,12 @@ pub struct Spritesheet {
 impl Spritesheet {
     pub fn from_4bpp(file_path: &str) -> Result<Spritesheet, std::io::Error> {
         let bytes = fs::read(file_path)?;
+
+        Ok(Spritesheet::from_4bpp_bytes(&bytes))
+    }
+
+    // Trailing bytes that don't make up a whole tile are ignored
+    pub fn from_4bpp_bytes(bytes: &[u8]) -> Spritesheet {
         let mut sprites: Vec<Sprite> = Vec::new();
         let mut i = 0;
         
@@ -41,8 +55,39 @@ impl Spritesheet {
             i += 1;
         }
 
-        Ok(Spritesheet {
+        Spritesheet {
             sprites
-        })
+        }
     }
+
+    // One 8x8 texture per tile colored with a single palette, in the layout OAM::draw indexes into
+    pub fn to_textures(&self, ctx: &Context, colors: &[RGB]) -> Vec<TextureHandle> {
+        let mut textures = Vec::new();
+
+        for (i, sprite) in self.sprites.iter().enumerate() {
+            let mut pixels: Vec<u8> = Vec::new();
+        
+            for &palette_id in &sprite.pixels {
+                // Color 0 is transparent, as are colors the palette doesn't have
+                match colors.get(palette_id as usize) {
+                    Some(rgb) if palette_id != 0 => pixels.extend([rgb.r, rgb.g, rgb.b, 255]),
+                    _ => pixels.extend([0, 0, 0, 0])
+                }
+            }
+            
+            textures.push(
+                ctx.load_texture(
+                i.to_string(),
+                ColorImage::from_rgba_unmultiplied([8, 8], &pixels), 
+                PIXEL_ART_TEXTURE_OPTIONS)
+            )
+        }
+
+        textures
+    }
+}
+
+// Straight from raw 4bpp tile data, e.g. read out of a ROM, to textures
+pub fn textures_from_4bpp(ctx: &Context, bytes: &[u8], colors: &[RGB]) -> Vec<TextureHandle> {
+    Spritesheet::from_4bpp_bytes(bytes).to_textures(ctx, colors)
 }
\ No newline at end of file